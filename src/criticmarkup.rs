@@ -0,0 +1,182 @@
+use crate::node::{Node, NodeKind, Chunk};
+
+use anyhow::{Result, bail};
+use regex::Regex;
+
+
+/// Make a Node from text in [CriticMarkup](https://criticmarkup.com) format.
+///
+/// Additions `{++ ++}`, deletions `{-- --}` and comments `{>> <<}` become
+/// the matching suggestions. A substitution `{~~old~>new~~}` becomes a
+/// deletion followed by an addition. Highlights `{== ==}` become plain text.
+/// A `@handle` at the end of the markup is used as the author, just like
+/// in a suggestions file.
+///
+/// # Examples
+///
+/// ```rust
+/// # use suggestions::make_node_from_criticmarkup;
+/// let node = make_node_from_criticmarkup(
+///     "The {~~cat~>dog @bob~~} sat{>>Really? @alice<<}.").unwrap();
+/// assert_eq!(node.to_string_suggestion(),
+///     "The --[cat @bob]--++[dog @bob]++ sat%%[Really? @alice]%%.");
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if the text contains an opening tag without a
+/// matching closing tag.
+pub fn make_node_from_criticmarkup(text: &str) -> Result<Node> {
+    let re = Regex::new(r"(?s)\{\+\+(?<add>.*?)\+\+\}|\{--(?<del>.*?)--\}|\{~~(?<old>.*?)~>(?<new>.*?)~~\}|\{>>(?<comment>.*?)<<\}|\{==(?<highlight>.*?)==\}").unwrap();
+    let re_opener = Regex::new(r"\{(\+\+|--|~~|>>|==)").unwrap();
+    let mut root = Node::root();
+    let mut pos = 0;
+
+    for caps in re.captures_iter(text) {
+        let all = caps.get(0).unwrap();
+        push_plain_text(&mut root, text, pos, all.start(), &re_opener)?;
+        pos = all.end();
+
+        if let Some(m) = caps.name("add") {
+            root.contents.push(Chunk::NodeChunk(make_node(NodeKind::Addition, m.as_str())));
+        } else if let Some(m) = caps.name("del") {
+            root.contents.push(Chunk::NodeChunk(make_node(NodeKind::Deletion, m.as_str())));
+        } else if let Some(m) = caps.name("comment") {
+            root.contents.push(Chunk::NodeChunk(make_node(NodeKind::Comment, m.as_str())));
+        } else if let Some(m) = caps.name("highlight") {
+            push_plain_text(&mut root, text, m.start(), m.end(), &re_opener)?;
+        } else {
+            let addition = make_node(NodeKind::Addition, &caps["new"]);
+            let mut deletion = make_node(NodeKind::Deletion, &caps["old"]);
+            deletion.author_string = addition.author_string.clone();
+            root.contents.push(Chunk::NodeChunk(deletion));
+            root.contents.push(Chunk::NodeChunk(addition));
+        }
+    }
+    push_plain_text(&mut root, text, pos, text.len(), &re_opener)?;
+
+    Ok(root)
+}
+
+
+/// Add `text[start..end]`, which should hold no markup, to `root`.
+fn push_plain_text(root: &mut Node, text: &str, start: usize, end: usize, re_opener: &Regex) -> Result<()> {
+    if let Some(m) = re_opener.find(&text[start..end]) {
+        let line = text[..start + m.start()].matches('\n').count() + 1;
+        bail!("Unmatched CriticMarkup tag '{}' at line {}", m.as_str(), line);
+    }
+    if start < end {
+        root.contents.push(Chunk::TextChunk(text[start..end].to_string()));
+    }
+    Ok(())
+}
+
+
+/// Make a node of `kind` from `text`, taking a final `@handle` as the author.
+/// The handle must start a word, so a final email address stays in the text.
+fn make_node(kind: NodeKind, text: &str) -> Node {
+    let re_author = Regex::new(r"(?s)^(?<text>.*?)(?<author>(?:^|\s) *@\S+\s*)$").unwrap();
+    let (text, author_string) = match re_author.captures(text) {
+        Some(caps) => (caps["text"].to_string(), Some(caps["author"].to_string())),
+        None => (text.to_string(), None)
+    };
+    let contents = if text.is_empty() { vec![] } else { vec![Chunk::TextChunk(text)] };
+    Node { author_string, contents, kind }
+}
+
+
+impl Node {
+    /// Return a string representing the Node in CriticMarkup format.
+    ///
+    /// A deletion followed directly by an addition by the same author
+    /// becomes a substitution `{~~old~>new~~}`. Authors are written as a
+    /// `@handle` at the end of the markup.
+    ///
+    /// # Errors
+    ///
+    /// CriticMarkup can't nest changes, so this returns an error if
+    /// there are additions or deletions inside other suggestions.
+    /// Comments inside suggestions are moved to just after them.
+    pub fn to_string_criticmarkup(&self) -> Result<String> {
+        let mut output = String::new();
+        let mut chunks = self.contents.iter().peekable();
+
+        while let Some(chunk) = chunks.next() {
+            let nd = match chunk {
+                Chunk::TextChunk(text) => {
+                    output.push_str(text);
+                    continue;
+                },
+                Chunk::NodeChunk(nd) => nd
+            };
+            let (text, mut comments) = flat_contents(nd)?;
+            let author = nd.author_string.as_deref().unwrap_or("");
+            match nd.kind {
+                NodeKind::Addition => output.push_str(&format!("{{++{}{}++}}", text, author)),
+                NodeKind::Comment => output.push_str(&format!("{{>>{}{}<<}}", text, author)),
+                NodeKind::Deletion => {
+                    let addition = chunks.next_if(|c| matches!(c, Chunk::NodeChunk(next)
+                        if next.kind == NodeKind::Addition && next.author_clean() == nd.author_clean()));
+                    if let Some(Chunk::NodeChunk(addition)) = addition {
+                        let (new_text, more) = flat_contents(addition)?;
+                        let author = addition.author_string.as_deref().unwrap_or("");
+                        output.push_str(&format!("{{~~{}~>{}{}~~}}", text, new_text, author));
+                        comments.extend(more);
+                    } else {
+                        output.push_str(&format!("{{--{}{}--}}", text, author));
+                    }
+                },
+                NodeKind::Root => unreachable!()
+            }
+            for comment in comments {
+                output.push_str(&comment);
+            }
+        }
+
+        Ok(output)
+    }
+}
+
+
+/// The text inside `node`, and any comments in it written as CriticMarkup.
+fn flat_contents(node: &Node) -> Result<(String, Vec<String>)> {
+    let mut text = String::new();
+    let mut comments = Vec::new();
+    for chunk in &node.contents {
+        match chunk {
+            Chunk::TextChunk(t) => text.push_str(t),
+            Chunk::NodeChunk(nd) if nd.kind == NodeKind::Comment => {
+                let (comment, _) = flat_contents(nd)?;
+                let author = nd.author_string.as_deref().unwrap_or("");
+                comments.push(format!("{{>>{}{}<<}}", comment, author));
+            },
+            Chunk::NodeChunk(nd) => bail!(
+                "CriticMarkup can't show nested suggestions like {:?}. Accept or reject them first.",
+                nd.to_string_suggestion())
+        }
+    }
+    Ok((text, comments))
+}
+
+
+#[test]
+fn test_criticmarkup() {
+    let text = "Plain {++added @a++} and {--gone--}.\n{~~old~>new @b~~} {==look==}{>>Hmm. @c<<}";
+    let node = make_node_from_criticmarkup(text).unwrap();
+    assert_eq!(node.to_string_suggestion(),
+        "Plain ++[added @a]++ and --[gone]--.\n--[old @b]--++[new @b]++ look%%[Hmm. @c]%%");
+    assert_eq!(node.to_string_criticmarkup().unwrap(),
+        "Plain {++added @a++} and {--gone--}.\n{~~old~>new @b~~} look{>>Hmm. @c<<}");
+
+    assert!(make_node_from_criticmarkup("Oops {++ no end").is_err());
+
+    let emails = make_node_from_criticmarkup(
+        "{>>Contact jane@example.com<<}{>>Or bob@example.com @c<<}").unwrap();
+    assert_eq!(emails.to_string_suggestion(), "%%[Contact jane@example.com]%%%%[Or bob@example.com @c]%%");
+
+    let nested = crate::make_node_from_string(
+        "A ++[big %%[Why? @b]%% @a]++ dog ++[and ++[a @b]++ cat]++".to_string()).unwrap();
+    assert!(nested.to_string_criticmarkup().is_err());
+    let commented = crate::make_node_from_string("A ++[big %%[Why? @b]%% @a]++ dog".to_string()).unwrap();
+    assert_eq!(commented.to_string_criticmarkup().unwrap(), "A {++big  @a++}{>>Why? @b<<} dog");
+}
@@ -0,0 +1,853 @@
+use crate::node::{Node, NodeKind, Chunk};
+
+use similar::{Algorithm, ChangeTag, DiffTag, capture_diff_deadline};
+use regex::Regex;
+
+use std::borrow::Cow;
+use std::io;
+use std::io::Read;
+use std::ops::Range;
+use std::time::{Duration, Instant};
+
+
+/// Options controlling how two texts are compared by [`diff_strings`]
+/// and [`diff_readers`].
+#[derive(Clone, Debug, Default)]
+pub struct DiffOptions {
+    /// Author handle to sign every change with, e.g. `"@author"`.
+    pub author: Option<String>,
+    /// The size of the pieces of text that are compared.
+    pub granularity: Granularity,
+    /// The diff algorithm to use.
+    pub algorithm: DiffAlgorithm,
+    /// If set, a diff which takes longer than this is abandoned and
+    /// redone by [`Granularity::Paragraphs`], with the same time limit.
+    /// If that runs out too, a quick approximate diff is returned.
+    /// [`diff_strings_with_warning`] says when this happens.
+    pub timeout: Option<Duration>,
+    /// If set, changes separated by unchanged text of at most this many
+    /// words (and no paragraph break) are merged into a single deletion
+    /// and addition. This turns a confetti of small changes into
+    /// something more readable.
+    pub coalesce: Option<usize>,
+    /// Which differences in whitespace are reported as changes.
+    pub whitespace: WhitespaceMode,
+    /// Where ignored whitespace differences are found, whose layout
+    /// to use in the output.
+    pub layout: Layout,
+}
+
+
+/// How differences in whitespace are treated.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum WhitespaceMode {
+    /// Every difference is reported.
+    #[default]
+    Exact,
+    /// Line breaks, indentation and runs of spaces are treated as a single
+    /// space, so rewrapped paragraphs show no changes. Paragraph breaks
+    /// still count.
+    IgnoreReflow,
+    /// All differences in whitespace are ignored.
+    Ignore,
+}
+
+
+/// Which text's layout to keep when whitespace differences are ignored.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Layout {
+    Old,
+    #[default]
+    New,
+}
+
+
+/// Algorithms for finding the difference between two texts.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum DiffAlgorithm {
+    /// Myers' algorithm: fast and usually gives the shortest diff.
+    #[default]
+    Myers,
+    /// Patience diff: often more readable when text has moved around.
+    Patience,
+    /// Longest common subsequence: slow, for small inputs.
+    Lcs,
+}
+
+
+impl From<DiffAlgorithm> for Algorithm {
+    fn from(alg: DiffAlgorithm) -> Algorithm {
+        match alg {
+            DiffAlgorithm::Myers => Algorithm::Myers,
+            DiffAlgorithm::Patience => Algorithm::Patience,
+            DiffAlgorithm::Lcs => Algorithm::Lcs
+        }
+    }
+}
+
+
+/// The unit of text compared by a diff. Changes are always reported
+/// as whole units, so coarser granularities give fewer, larger changes.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Granularity {
+    /// Individual characters: best for typo fixes.
+    Chars,
+    /// Words and the whitespace between them.
+    #[default]
+    Words,
+    /// Sentences, ending in `.`, `!` or `?`, or at a paragraph break.
+    Sentences,
+    /// Lines: best for code or data.
+    Lines,
+    /// Paragraphs separated by blank lines.
+    Paragraphs,
+}
+
+
+/// Return a Node tree representing the difference between two strings.
+/// 
+/// The tree can be transformed further before rendering it with, e.g.,
+/// [`Node::to_string_suggestion()`].
+/// 
+/// # Examples
+/// 
+/// ```rust
+/// # use suggestions::{diff_strings, DiffOptions};
+/// let node = diff_strings("The old text.", "The new text.", &DiffOptions::default());
+/// assert_eq!(node.to_string_suggestion(), "The --[old]--++[new]++ text.");
+/// ```
+pub fn diff_strings(old: &str, new: &str, opts: &DiffOptions) -> Node {
+    diff_strings_with_warning(old, new, opts).0
+}
+
+
+/// Like [`diff_strings`], but also returns a warning if the diff ran out
+/// of time and fell back to comparing paragraphs.
+/// 
+/// # Examples
+/// 
+/// ```rust
+/// # use suggestions::{diff_strings_with_warning, DiffOptions};
+/// # use std::time::Duration;
+/// let opts = DiffOptions { timeout: Some(Duration::ZERO), ..Default::default() };
+/// let (node, warning) = diff_strings_with_warning("The old text.", "The new text.", &opts);
+/// assert_eq!(node.to_string_suggestion(), "--[The old text.]--++[The new text.]++");
+/// assert!(warning.is_some());
+/// ```
+pub fn diff_strings_with_warning(old: &str, new: &str, opts: &DiffOptions) -> (Node, Option<String>) {
+    let (segments, warning) = diff_segments(old, new, opts);
+    (make_node_from_diffs(flatten_segments(segments), opts.author.clone()), warning)
+}
+
+
+/// Return `base` with the differences between its accepted text and
+/// `new` added as further suggestions.
+/// 
+/// Existing suggestions and their authors are kept. New changes to text
+/// inside an existing addition are nested inside it. Unchanged text keeps
+/// the layout of `base`, whatever `opts.layout` says.
+/// 
+/// # Examples
+/// 
+/// ```rust
+/// # use suggestions::{diff_onto_node, make_node_from_string, DiffOptions};
+/// let base = make_node_from_string(
+///     "The ++[big red @alice]++ car.".to_string()).unwrap();
+/// let opts = DiffOptions { author: Some("@bob".to_string()), ..Default::default() };
+/// let node = diff_onto_node(base, "The big blue car.", &opts);
+/// assert_eq!(node.to_string_suggestion(), 
+///     "The ++[big --[red @bob ]--++[blue @bob ]++ @alice]++ car.");
+/// ```
+pub fn diff_onto_node(base: Node, new: &str, opts: &DiffOptions) -> Node {
+    diff_onto_node_with_warning(base, new, opts).0
+}
+
+
+/// Like [`diff_onto_node`], but also returns a warning if the diff ran
+/// out of time and fell back to comparing paragraphs.
+pub fn diff_onto_node_with_warning(base: Node, new: &str, opts: &DiffOptions) -> (Node, Option<String>) {
+    let opts = DiffOptions { layout: Layout::Old, ..opts.clone() };
+    let (segments, warning) = diff_segments(&base.to_string_accept(), new, &opts);
+
+    let mut merger = Merger {
+        deletions: Vec::new(),
+        insertions: Vec::new(),
+        author_string: opts.author.map(|a| format!(" {} ", a)),
+        pos: 0,
+        next_deletion: 0,
+        next_insertion: 0
+    };
+    let mut pos = 0;
+    for segment in segments {
+        match segment {
+            Segment::Same(same) => pos += same.len(),
+            Segment::Changed(del, ins) => {
+                if ! del.is_empty() {
+                    merger.deletions.push(pos..pos + del.len());
+                    pos += del.len();
+                }
+                if ! ins.is_empty() {
+                    merger.insertions.push((pos, ins));
+                }
+            }
+        }
+    }
+
+    (merger.merge_node(base, true), warning)
+}
+
+
+/// Compare `old` and `new`. If `opts.timeout` runs out, paragraphs are
+/// compared instead, and a warning is returned.
+pub(crate) fn diff_segments(old: &str, new: &str, opts: &DiffOptions) -> (Vec<Segment>, Option<String>) {
+    let deadline = || opts.timeout.map(|t| Instant::now() + t);
+    let timed_out = |deadline: Option<Instant>| deadline.is_some_and(|d| Instant::now() >= d);
+
+    let first_deadline = deadline();
+    let mut diffs = diff_changes(old, new, opts.granularity, opts, first_deadline);
+    let mut warning = None;
+    if timed_out(first_deadline) {
+        if opts.granularity != Granularity::Paragraphs {
+            diffs = diff_changes(old, new, Granularity::Paragraphs, opts, deadline());
+            warning = Some("Diff timed out, so changes are shown by paragraphs".to_string());
+        } else {
+            warning = Some("Diff timed out, so changes may be larger than needed".to_string());
+        }
+    }
+    let mut segments = make_segments(diffs);
+    if opts.whitespace != WhitespaceMode::Exact {
+        segments = ignore_whitespace_changes(segments, opts);
+    }
+    if let Some(max_words) = opts.coalesce {
+        segments = coalesce_segments(segments, max_words);
+    }
+    (slide_spaces(segments), warning)
+}
+
+
+/// Adds deletions and insertions, given as byte offsets into the
+/// accepted text of a Node tree, to the tree itself.
+struct Merger {
+    deletions: Vec<Range<usize>>,
+    insertions: Vec<(usize, String)>,
+    author_string: Option<String>,
+    // offset into the accepted text
+    pos: usize,
+    // the first deletion that doesn't end before `pos`
+    next_deletion: usize,
+    next_insertion: usize
+}
+
+
+impl Merger {
+    fn merge_node(&mut self, node: Node, visible: bool) -> Node {
+        // Text is part of the accepted text unless it's inside
+        // a deletion or comment
+        let visible = visible && matches!(node.kind, NodeKind::Root | NodeKind::Addition);
+        let mut contents = Vec::new();
+        if node.kind == NodeKind::Root {
+            self.flush_insertions(&mut contents);
+        }
+
+        for chunk in node.contents {
+            match chunk {
+                Chunk::TextChunk(text) if visible => self.merge_text(&text, &mut contents),
+                Chunk::NodeChunk(nd) => contents.push(Chunk::NodeChunk(self.merge_node(nd, visible))),
+                chunk => contents.push(chunk)
+            }
+        }
+
+        if node.kind == NodeKind::Root {
+            let remaining = self.insertions.split_off(self.next_insertion);
+            for (_, text) in remaining {
+                contents.push(Chunk::NodeChunk(self.new_node(NodeKind::Addition, text)));
+            }
+        }
+
+        Node { contents, ..node }
+    }
+
+    /// Split `text` at the edges of deletions and at insertions, wrapping
+    /// deleted pieces in new Deletion nodes and adding new Addition nodes.
+    fn merge_text(&mut self, text: &str, contents: &mut Vec<Chunk>) {
+        let start = self.pos;
+        let end = start + text.len();
+
+        while self.pos < end {
+            while self.deletions.get(self.next_deletion).is_some_and(|d| d.end <= self.pos) {
+                self.next_deletion += 1;
+            }
+            let deletion = self.deletions.get(self.next_deletion);
+            let deleted = deletion.is_some_and(|d| d.start <= self.pos);
+            let mut boundary = end;
+            if let Some(d) = deletion {
+                boundary = boundary.min(if deleted { d.end } else { d.start });
+            }
+            if let Some((p, _)) = self.insertions.get(self.next_insertion) {
+                if *p > self.pos {
+                    boundary = boundary.min(*p);
+                }
+            }
+
+            let piece = text[self.pos - start..boundary - start].to_string();
+            if deleted {
+                contents.push(Chunk::NodeChunk(self.new_node(NodeKind::Deletion, piece)));
+            } else {
+                contents.push(Chunk::TextChunk(piece));
+            }
+            self.pos = boundary;
+            self.flush_insertions(contents);
+        }
+    }
+
+    fn flush_insertions(&mut self, contents: &mut Vec<Chunk>) {
+        while let Some((p, text)) = self.insertions.get(self.next_insertion) {
+            if *p > self.pos {
+                break;
+            }
+            let nd = self.new_node(NodeKind::Addition, text.clone());
+            contents.push(Chunk::NodeChunk(nd));
+            self.next_insertion += 1;
+        }
+    }
+
+    fn new_node(&self, kind: NodeKind, text: String) -> Node {
+        Node {
+            kind,
+            contents: vec![Chunk::TextChunk(text)],
+            author_string: self.author_string.clone()
+        }
+    }
+}
+
+
+/// Return a Node tree representing the difference between the contents
+/// of two readers.
+/// 
+/// # Errors
+/// 
+/// Returns an error if either reader fails or does not contain valid UTF-8.
+pub fn diff_readers<R: Read, S: Read>(
+    mut old: R, 
+    mut new: S, 
+    opts: &DiffOptions
+) -> io::Result<Node> {
+    let mut contents_old = String::new();
+    let mut contents_new = String::new();
+    old.read_to_string(&mut contents_old)?;
+    new.read_to_string(&mut contents_new)?;
+
+    Ok(diff_strings(&contents_old, &contents_new, opts))
+}
+
+
+fn diff_changes<'a>(
+    old: &'a str, 
+    new: &'a str, 
+    granularity: Granularity,
+    opts: &DiffOptions,
+    deadline: Option<Instant>
+) -> Vec<(ChangeTag, &'a str)> {
+    let old_tokens = tokenize(old, granularity);
+    let new_tokens = tokenize(new, granularity);
+    // Tokens are compared by their keys, which may ignore whitespace
+    let old_keys: Vec<Cow<str>> = old_tokens.iter()
+        .map(|t| normalize_whitespace(t, opts.whitespace))
+        .collect();
+    let new_keys: Vec<Cow<str>> = new_tokens.iter()
+        .map(|t| normalize_whitespace(t, opts.whitespace))
+        .collect();
+
+    let ops = capture_diff_deadline(opts.algorithm.into(), 
+        &old_keys, 0..old_keys.len(), 
+        &new_keys, 0..new_keys.len(), 
+        deadline);
+
+    let old_offsets = token_offsets(&old_tokens);
+    let new_offsets = token_offsets(&new_tokens);
+    let old_text = |r: Range<usize>| &old[old_offsets[r.start]..old_offsets[r.end]];
+    let new_text = |r: Range<usize>| &new[new_offsets[r.start]..new_offsets[r.end]];
+
+    let mut changes = Vec::new();
+    for op in ops {
+        let (tag, old_range, new_range) = op.as_tag_tuple();
+        match tag {
+            DiffTag::Equal => match opts.layout {
+                Layout::Old => changes.push((ChangeTag::Equal, old_text(old_range))),
+                Layout::New => changes.push((ChangeTag::Equal, new_text(new_range)))
+            },
+            DiffTag::Delete => changes.push((ChangeTag::Delete, old_text(old_range))),
+            DiffTag::Insert => changes.push((ChangeTag::Insert, new_text(new_range))),
+            DiffTag::Replace => {
+                changes.push((ChangeTag::Delete, old_text(old_range)));
+                changes.push((ChangeTag::Insert, new_text(new_range)));
+            }
+        }
+    }
+
+    changes
+}
+
+
+/// Split `text` into pieces of the given granularity. The pieces,
+/// concatenated, give back `text`.
+fn tokenize(text: &str, granularity: Granularity) -> Vec<&str> {
+    match granularity {
+        Granularity::Chars => text.split_inclusive(|_| true).collect(),
+        Granularity::Words => split_words(text),
+        Granularity::Sentences => split_sentences(text),
+        Granularity::Lines => text.split_inclusive('\n').collect(),
+        Granularity::Paragraphs => split_paragraphs(text)
+    }
+}
+
+
+/// Byte offsets of the start of each token, plus the end of the last one.
+fn token_offsets(tokens: &[&str]) -> Vec<usize> {
+    let mut offsets = vec![0];
+    for token in tokens {
+        offsets.push(offsets.last().unwrap() + token.len());
+    }
+    offsets
+}
+
+
+/// Split `text` into words and the whitespace between them.
+fn split_words(text: &str) -> Vec<&str> {
+    let re_word = Regex::new(r"\s+|\S+").unwrap();
+    re_word.find_iter(text).map(|m| m.as_str()).collect()
+}
+
+
+/// Rewrite whitespace in `text` so that texts which differ only in
+/// ignored whitespace compare equal.
+fn normalize_whitespace(text: &str, mode: WhitespaceMode) -> Cow<'_, str> {
+    if mode == WhitespaceMode::Exact || ! text.contains(char::is_whitespace) {
+        return Cow::Borrowed(text);
+    }
+
+    let mut output = String::with_capacity(text.len());
+    let mut newlines: Option<usize> = None; // Some(n) while in a whitespace run
+    for c in text.chars().chain(std::iter::once('x')) {
+        if c.is_whitespace() {
+            *newlines.get_or_insert(0) += usize::from(c == '\n');
+            continue;
+        }
+        if let (Some(n), WhitespaceMode::IgnoreReflow) = (newlines.take(), mode) {
+            output.push_str(if n > 1 { "\n\n" } else { " " });
+        }
+        output.push(c);
+    }
+    output.pop();
+
+    Cow::Owned(output)
+}
+
+
+/// Words which end in a period but usually don't end a sentence.
+const ABBREVIATIONS: [&str; 22] = [
+    "al", "approx", "ca", "cf", "dr", "e.g", "eq", "fig", "i.e", "jr", "mr", 
+    "mrs", "ms", "no", "p", "pp", "prof", "sr", "st", "vol", "vs", "viz"
+];
+
+
+/// Split `text` into sentences and the whitespace between them.
+/// The pieces, concatenated, give back `text`.
+fn split_sentences(text: &str) -> Vec<&str> {
+    let re_space = Regex::new(r"\s+").unwrap();
+    let mut tokens = Vec::new();
+    let mut start = 0;
+
+    for space in re_space.find_iter(text) {
+        let sentence = &text[start..space.start()];
+        let next_char = text[space.end()..].chars().next();
+        let paragraph_break = space.as_str().matches('\n').count() > 1;
+        if sentence.is_empty() || ! (paragraph_break || ends_sentence(sentence, next_char)) {
+            continue;
+        }
+        tokens.push(sentence);
+        tokens.push(space.as_str());
+        start = space.end();
+    }
+    if start < text.len() {
+        tokens.push(&text[start..]);
+    }
+
+    tokens
+}
+
+
+fn ends_sentence(sentence: &str, next_char: Option<char>) -> bool {
+    let trimmed = sentence.trim_end_matches(['"', '\'', ')', ']', '\u{2019}', '\u{201D}']);
+    if ! trimmed.ends_with(['.', '!', '?']) {
+        return false;
+    }
+    if next_char.is_some_and(char::is_lowercase) {
+        return false;
+    }
+    if trimmed.ends_with('.') {
+        let last_word = trimmed
+            .rsplit(char::is_whitespace)
+            .next()
+            .unwrap_or("")
+            .trim_start_matches(|c: char| ! c.is_alphanumeric())
+            .trim_end_matches('.')
+            .to_lowercase();
+        let is_initial = last_word.chars().count() == 1 && 
+            last_word.chars().all(char::is_alphabetic);
+        if is_initial || ABBREVIATIONS.contains(&last_word.as_str()) {
+            return false;
+        }
+    }
+
+    true
+}
+
+
+/// Split `text` into paragraphs and the blank lines between them,
+/// keeping any trailing whitespace separate. The pieces, concatenated,
+/// give back `text`.
+fn split_paragraphs(text: &str) -> Vec<&str> {
+    let re_break = Regex::new(r"\s*\n[ \t\r]*\n\s*|\s+$").unwrap();
+    let mut tokens = Vec::new();
+    let mut start = 0;
+
+    for brk in re_break.find_iter(text) {
+        if brk.start() > start {
+            tokens.push(&text[start..brk.start()]);
+        }
+        tokens.push(brk.as_str());
+        start = brk.end();
+    }
+    if start < text.len() {
+        tokens.push(&text[start..]);
+    }
+
+    tokens
+}
+
+
+/// A run of unchanged text, or a replacement of some deleted text
+/// by some added text (either may be empty).
+pub(crate) enum Segment {
+    Same(String),
+    Changed(String, String)
+}
+
+
+fn make_segments(changes: Vec<(ChangeTag, &str)>) -> Vec<Segment> {
+    let mut segments: Vec<Segment> = Vec::new();
+    for (tag, text) in changes {
+        match (tag, segments.last_mut()) {
+            (ChangeTag::Equal, Some(Segment::Same(same))) => same.push_str(text),
+            (ChangeTag::Delete, Some(Segment::Changed(del, _))) => del.push_str(text),
+            (ChangeTag::Insert, Some(Segment::Changed(_, ins))) => ins.push_str(text),
+            (ChangeTag::Equal, _) => segments.push(Segment::Same(text.to_string())),
+            (ChangeTag::Delete, _) => segments.push(Segment::Changed(text.to_string(), String::new())),
+            (ChangeTag::Insert, _) => segments.push(Segment::Changed(String::new(), text.to_string())),
+        }
+    }
+    segments
+}
+
+
+/// Turn changes which only differ in ignored whitespace into
+/// unchanged text, using the layout chosen in `opts`.
+fn ignore_whitespace_changes(segments: Vec<Segment>, opts: &DiffOptions) -> Vec<Segment> {
+    let mut output: Vec<Segment> = Vec::new();
+    for segment in segments {
+        let segment = match segment {
+            Segment::Changed(del, ins) if 
+                normalize_whitespace(&del, opts.whitespace) == 
+                    normalize_whitespace(&ins, opts.whitespace) => 
+                match opts.layout {
+                    Layout::Old => Segment::Same(del),
+                    Layout::New => Segment::Same(ins)
+                },
+            _ => segment
+        };
+        match (segment, output.last_mut()) {
+            (Segment::Same(same), Some(Segment::Same(prev))) => prev.push_str(&same),
+            (segment, _) => output.push(segment)
+        }
+    }
+    output
+}
+
+
+/// Merge changes separated by unchanged text of at most `max_words` words,
+/// but never across a paragraph break. The unchanged text becomes part
+/// of both the deletion and the addition.
+fn coalesce_segments(segments: Vec<Segment>, max_words: usize) -> Vec<Segment> {
+    let mut merged: Vec<Segment> = Vec::new();
+    for segment in segments {
+        let absorb = match (&segment, merged.as_slice()) {
+            (Segment::Changed(..), [.., Segment::Changed(..), Segment::Same(same)]) => 
+                same.split_whitespace().count() <= max_words && 
+                    same.matches('\n').count() < 2,
+            _ => false
+        };
+        if ! absorb {
+            merged.push(segment);
+            continue;
+        }
+        let (Some(Segment::Same(same)), Segment::Changed(del, ins)) = (merged.pop(), segment) else {
+            unreachable!()
+        };
+        if let Some(Segment::Changed(prev_del, prev_ins)) = merged.last_mut() {
+            prev_del.push_str(&same);
+            prev_del.push_str(&del);
+            prev_ins.push_str(&same);
+            prev_ins.push_str(&ins);
+        }
+    }
+    merged
+}
+
+
+/// Where a change ends in spaces and the text before it ends in the
+/// same spaces, move them to the start of the change, so that changes
+/// end with a word: `a ++[b ]++c` becomes `a++[ b]++ c`.
+fn slide_spaces(segments: Vec<Segment>) -> Vec<Segment> {
+    let mut output: Vec<Segment> = Vec::new();
+    let mut carried = String::new();
+    for segment in segments {
+        let segment = match segment {
+            Segment::Same(same) => Segment::Same(std::mem::take(&mut carried) + &same),
+            Segment::Changed(mut del, mut ins) => {
+                if ! carried.is_empty() {
+                    output.push(Segment::Same(std::mem::take(&mut carried)));
+                }
+                if let Some(Segment::Same(same)) = output.last_mut() {
+                    while same.ends_with(' ') && 
+                        [&del, &ins].iter().all(|t| t.is_empty() || t.ends_with(' ')) {
+                        same.pop();
+                        for text in [&mut del, &mut ins] {
+                            if text.pop().is_some() {
+                                text.insert(0, ' ');
+                            }
+                        }
+                        carried.push(' ');
+                    }
+                }
+                Segment::Changed(del, ins)
+            }
+        };
+        output.push(segment);
+    }
+    if ! carried.is_empty() {
+        output.push(Segment::Same(carried));
+    }
+    output.retain(|segment| ! matches!(segment, Segment::Same(same) if same.is_empty()));
+    output
+}
+
+
+fn flatten_segments(segments: Vec<Segment>) -> Vec<(ChangeTag, String)> {
+    let mut output = Vec::new();
+    for segment in segments {
+        match segment {
+            Segment::Same(same) => output.push((ChangeTag::Equal, same)),
+            Segment::Changed(del, ins) => {
+                if ! del.is_empty() {
+                    output.push((ChangeTag::Delete, del));
+                }
+                if ! ins.is_empty() {
+                    output.push((ChangeTag::Insert, ins));
+                }
+            }
+        }
+    }
+
+    output
+}
+
+
+fn make_node_from_diffs<S: AsRef<str>>(changes: Vec<(ChangeTag, S)>, author: Option<String>) -> Node {
+    let author_string = author.map(|a| format!(" {} ", a));
+
+    let mut root = Node::root();
+    
+    for change in changes {
+        match change {
+            (ChangeTag::Equal, text) => {
+                root.contents.push(Chunk::TextChunk(text.as_ref().to_string()));    
+            },
+            (ChangeTag::Insert, text) => {
+                let nd = Node {
+                    kind: NodeKind::Addition,
+                    contents: vec![Chunk::TextChunk(text.as_ref().to_string())],
+                    author_string: author_string.clone()
+                };
+                root.contents.push(Chunk::NodeChunk(nd));
+            },
+            (ChangeTag::Delete, text) => {
+                let nd = Node {
+                    kind: NodeKind::Deletion,
+                    contents: vec![Chunk::TextChunk(text.as_ref().to_string())],
+                    author_string: author_string.clone()
+                };
+                root.contents.push(Chunk::NodeChunk(nd));
+            }
+        };
+        
+    }
+
+    root
+}
+
+
+#[test]
+fn test_diff_readers() {
+    let old = "Some old text.".as_bytes();
+    let new = "Some new text.".as_bytes();
+    let opts = DiffOptions { 
+        author: Some("@me".to_string()), 
+        ..Default::default() 
+    };
+    let node = diff_readers(old, new, &opts).unwrap();
+    assert_eq!(node.to_string_reject(), "Some old text.");
+    assert_eq!(node.to_string_accept(), "Some new text.");
+    assert_eq!(node.to_string_suggestion(), "Some --[old @me ]--++[new @me ]++ text.");
+}
+
+
+#[test]
+fn test_signed_changes_round_trip() {
+    let opts = DiffOptions { author: Some("@me".to_string()), ..Default::default() };
+    let old = "It was a dark night, e.g. for him.";
+    let new = "Yes. It was a dark stormy night, for him.";
+    let node = diff_strings(old, new, &opts);
+    assert_eq!(node.to_string_suggestion(), 
+        "++[Yes.  @me ]++It was a dark++[ stormy @me ]++ night,--[ e.g. @me ]-- for him.");
+
+    let parsed = crate::make_node_from_string(node.to_string_suggestion()).unwrap();
+    assert_eq!(parsed.to_string_reject(), old);
+    assert_eq!(parsed.to_string_accept(), new);
+}
+
+
+#[test]
+fn test_split_sentences() {
+    let text = "Dr. Smith arrived, e.g. at noon. He left! Did he?  \"Yes.\" Mr. J. Bloggs stayed.\n\nA heading\n\nMore.";
+    assert_eq!(split_sentences(text), vec![
+        "Dr. Smith arrived, e.g. at noon.", " ", 
+        "He left!", " ",
+        "Did he?", "  ",
+        "\"Yes.\"", " ",
+        "Mr. J. Bloggs stayed.", "\n\n",
+        "A heading", "\n\n",
+        "More."
+    ]);
+    assert_eq!(split_sentences(text).concat(), text);
+}
+
+
+#[test]
+fn test_granularity() {
+    let old = "One sentence here. Another one.\n\nA second paragraph.";
+    let new = "One sentence there. Another one.\n\nA second paragraph.";
+    let mut opts = DiffOptions { 
+        granularity: Granularity::Chars, 
+        ..Default::default() 
+    };
+
+    assert_eq!(diff_strings(old, new, &opts).to_string_suggestion(), 
+        "One sentence ++[t]++here. Another one.\n\nA second paragraph.");
+    opts.granularity = Granularity::Sentences;
+    assert_eq!(diff_strings(old, new, &opts).to_string_suggestion(), 
+        "--[One sentence here.]--++[One sentence there.]++ Another one.\n\nA second paragraph.");
+    opts.granularity = Granularity::Paragraphs;
+    assert_eq!(diff_strings(old, new, &opts).to_string_suggestion(), 
+        "--[One sentence here. Another one.]--++[One sentence there. Another one.]++\n\nA second paragraph.");
+}
+
+
+#[test]
+fn test_algorithm_and_timeout() {
+    let old = "One sentence here. Another one.\n\nA second paragraph.";
+    let new = "One sentence there. Another one.\n\nA second paragraph.";
+    for algorithm in [DiffAlgorithm::Myers, DiffAlgorithm::Patience, DiffAlgorithm::Lcs] {
+        let opts = DiffOptions { algorithm, ..Default::default() };
+        assert_eq!(diff_strings(old, new, &opts).to_string_suggestion(), 
+            "One sentence --[here.]--++[there.]++ Another one.\n\nA second paragraph.");
+    }
+
+    // a zero timeout always expires, so we fall back to paragraphs
+    let opts = DiffOptions { timeout: Some(Duration::ZERO), ..Default::default() };
+    let (node, warning) = diff_strings_with_warning(old, new, &opts);
+    assert_eq!(warning.as_deref(), Some("Diff timed out, so changes are shown by paragraphs"));
+    assert_eq!(node.to_string_reject(), old);
+    assert_eq!(node.to_string_accept(), new);
+    assert!(node.to_string_suggestion().starts_with("--[One sentence here. Another one.]--"));
+
+    let opts = DiffOptions { granularity: Granularity::Paragraphs, ..opts };
+    let (_, warning) = diff_strings_with_warning(old, new, &opts);
+    assert_eq!(warning.as_deref(), Some("Diff timed out, so changes may be larger than needed"));
+}
+
+
+#[test]
+fn test_coalesce() {
+    let old = "The quick brown fox jumps over the lazy dog.";
+    let new = "A slow brown cat jumps over the lazy dog.";
+    let mut opts = DiffOptions::default();
+    assert_eq!(diff_strings(old, new, &opts).to_string_suggestion(), 
+        "--[The]--++[A]++ --[quick]--++[slow]++ brown --[fox]--++[cat]++ jumps over the lazy dog.");
+
+    opts.coalesce = Some(0);
+    assert_eq!(diff_strings(old, new, &opts).to_string_suggestion(), 
+        "--[The quick]--++[A slow]++ brown --[fox]--++[cat]++ jumps over the lazy dog.");
+
+    opts.coalesce = Some(1);
+    let node = diff_strings(old, new, &opts);
+    assert_eq!(node.to_string_suggestion(), 
+        "--[The quick brown fox]--++[A slow brown cat]++ jumps over the lazy dog.");
+    assert_eq!(node.to_string_reject(), old);
+    assert_eq!(node.to_string_accept(), new);
+}
+
+
+#[test]
+fn test_ignore_whitespace() {
+    let old = "A paragraph which\nhas been  rewrapped.\n\nAnother one.";
+    let new = "A paragraph which has\nbeen rewrapped.\n\nAnother   one,\nchanged.";
+    let mut opts = DiffOptions { 
+        whitespace: WhitespaceMode::IgnoreReflow, 
+        ..Default::default() 
+    };
+    let node = diff_strings(old, new, &opts);
+    assert_eq!(node.to_string_suggestion(), 
+        "A paragraph which has\nbeen rewrapped.\n\nAnother   --[one.]--++[one,\nchanged.]++");
+    assert_eq!(node.to_string_accept(), new);
+
+    opts.layout = Layout::Old;
+    assert_eq!(diff_strings(old, new, &opts).to_string_suggestion(), 
+        "A paragraph which\nhas been  rewrapped.\n\nAnother --[one.]--++[one,\nchanged.]++");
+
+    // reflow doesn't ignore paragraph breaks, or removed whitespace
+    let old = "Some text.\n\nMore text. Done";
+    let new = "Some text. More text.Done";
+    opts.layout = Layout::New;
+    assert_eq!(diff_strings(old, new, &opts).to_string_suggestion(), 
+        "Some text.--[\n\n]--++[ ]++More --[text. Done]--++[text.Done]++");
+    opts.whitespace = WhitespaceMode::Ignore;
+    assert_eq!(diff_strings(old, new, &opts).to_string_suggestion(), new);
+}
+
+
+#[test]
+fn test_diff_onto_node() {
+    let base = crate::make_node_from_string(
+        "Start. ++[Added by A. @a]++ --[Deleted by A. @a]--%%[Hmm. @a]%%End.".to_string()
+    ).unwrap();
+    let new = "Yes. Start. Added by B. End. Finish.";
+    let opts = DiffOptions { author: Some("@b".to_string()), ..Default::default() };
+    let node = diff_onto_node(base, new, &opts);
+
+    assert_eq!(node.to_string_suggestion(), 
+        "++[Yes.  @b ]++Start. ++[Added by --[A. @b ]--++[B. @b ]++ @a]++ \
+        --[Deleted by A. @a]--%%[Hmm. @a]%%End.++[ Finish. @b ]++");
+    assert_eq!(node.to_string_accept(), new);
+    assert_eq!(node.to_string_reject(), "Start.  Deleted by A.End.");
+}
@@ -0,0 +1,365 @@
+use crate::node::{Node, NodeKind};
+
+use crate::xml::{Piece, Change, Builder, UNKNOWN_AUTHOR, flatten, escape, read_zip_file, attribute, handle_from};
+
+use anyhow::{Result, Context};
+use quick_xml::events::{Event, BytesStart};
+use quick_xml::reader::Reader;
+use regex::Regex;
+use zip::ZipArchive;
+use zip::write::{ZipWriter, SimpleFileOptions};
+
+use std::collections::{HashMap, HashSet};
+use std::io::{Read, Seek, Write};
+
+
+const NAMESPACE: &str = "http://schemas.openxmlformats.org/wordprocessingml/2006/main";
+
+const CONTENT_TYPES: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types">
+<Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/>
+<Default Extension="xml" ContentType="application/xml"/>
+<Override PartName="/word/document.xml" ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.document.main+xml"/>
+<Override PartName="/word/comments.xml" ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.comments+xml"/>
+</Types>"#;
+
+const PACKAGE_RELS: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">
+<Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument" Target="word/document.xml"/>
+</Relationships>"#;
+
+const DOCUMENT_RELS: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">
+<Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/comments" Target="comments.xml"/>
+</Relationships>"#;
+
+
+/// A paragraph of pieces. `mark` is the change, if any, that the
+/// paragraph break after it belongs to.
+#[derive(Default)]
+struct Paragraph {
+    pieces: Vec<Piece>,
+    mark: Option<Change>
+}
+
+
+/// Write `node` as a Word document, with additions and deletions as
+/// tracked changes and comments as Word comments.
+///
+/// Paragraphs are separated by blank lines. Other line breaks become
+/// spaces. Word can't nest changes, so text inside several suggestions
+/// is marked with the innermost one; deleted text stays deleted.
+/// Unsigned suggestions get the author "Unknown".
+///
+/// # Examples
+///
+/// ```rust
+/// # use suggestions::{make_node_from_string, write_docx};
+/// let node = make_node_from_string("Hello ++[world @alice]++!".to_string()).unwrap();
+/// let mut docx = std::io::Cursor::new(Vec::new());
+/// write_docx(&node, &mut docx).unwrap();
+/// ```
+pub fn write_docx<W: Write + Seek>(node: &Node, writer: W) -> Result<()> {
+    let mut pieces = Vec::new();
+    flatten(node, None, &mut pieces);
+    let paragraphs = split_paragraphs(pieces);
+
+    let mut ids = 0..;
+    let mut body = String::new();
+    let mut comments = String::new();
+    let mut comment_ids = 0..;
+    for paragraph in paragraphs {
+        body.push_str("<w:p>");
+        if let Some(change) = &paragraph.mark {
+            body.push_str(&format!("<w:pPr><w:rPr>{}</w:rPr></w:pPr>",
+                change_tag(change, ids.next().unwrap(), true)));
+        }
+        for piece in paragraph.pieces {
+            match piece {
+                Piece::Text(text, None) => body.push_str(&run(&text, "w:t")),
+                Piece::Text(text, Some(change)) => {
+                    let tag = if change.kind == NodeKind::Deletion { "w:delText" } else { "w:t" };
+                    body.push_str(&change_tag(&change, ids.next().unwrap(), false));
+                    body.push_str(&run(&text, tag));
+                    body.push_str(if change.kind == NodeKind::Deletion { "</w:del>" } else { "</w:ins>" });
+                },
+                Piece::Comment(text, author) => {
+                    let id = comment_ids.next().unwrap();
+                    body.push_str(&format!("<w:commentRangeStart w:id=\"{id}\"/><w:commentRangeEnd w:id=\"{id}\"/>\
+                        <w:r><w:commentReference w:id=\"{id}\"/></w:r>"));
+                    comments.push_str(&format!("<w:comment w:id=\"{}\" w:author=\"{}\"><w:p>{}</w:p></w:comment>",
+                        id, escape(author.as_deref().unwrap_or(UNKNOWN_AUTHOR)), run(&text, "w:t")));
+                }
+            }
+        }
+        body.push_str("</w:p>");
+    }
+
+    let document = format!("<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n\
+        <w:document xmlns:w=\"{}\"><w:body>{}<w:sectPr/></w:body></w:document>", NAMESPACE, body);
+    let comments = format!("<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n\
+        <w:comments xmlns:w=\"{}\">{}</w:comments>", NAMESPACE, comments);
+
+    let mut zip = ZipWriter::new(writer);
+    let options = SimpleFileOptions::default();
+    for (name, contents) in [
+        ("[Content_Types].xml", CONTENT_TYPES),
+        ("_rels/.rels", PACKAGE_RELS),
+        ("word/_rels/document.xml.rels", DOCUMENT_RELS),
+        ("word/document.xml", &document),
+        ("word/comments.xml", &comments)
+    ] {
+        zip.start_file(name, options)?;
+        zip.write_all(contents.as_bytes())?;
+    }
+    zip.finish()?;
+    Ok(())
+}
+
+
+/// Split pieces into paragraphs at blank lines.
+fn split_paragraphs(pieces: Vec<Piece>) -> Vec<Paragraph> {
+    let re_break = Regex::new(r"\s*\n[ \t\r]*\n\s*").unwrap();
+    let mut paragraphs = Vec::new();
+    let mut current = Paragraph::default();
+
+    for piece in pieces {
+        let Piece::Text(text, change) = piece else {
+            current.pieces.push(piece);
+            continue;
+        };
+        let mut pos = 0;
+        for m in re_break.find_iter(&text) {
+            push_line(&mut current, &text[pos..m.start()], &change);
+            // a break at the very start or end of the document isn't a paragraph
+            if ! (current.pieces.is_empty() && paragraphs.is_empty()) {
+                current.mark = change.clone();
+                paragraphs.push(std::mem::take(&mut current));
+            }
+            pos = m.end();
+        }
+        push_line(&mut current, &text[pos..], &change);
+    }
+    if ! current.pieces.is_empty() {
+        paragraphs.push(current);
+    }
+    paragraphs
+}
+
+
+fn push_line(paragraph: &mut Paragraph, text: &str, change: &Option<Change>) {
+    let text = if paragraph.pieces.is_empty() { text.trim_start() } else { text };
+    if ! text.is_empty() {
+        let text = text.replace("\r\n", " ").replace('\n', " ");
+        paragraph.pieces.push(Piece::Text(text, change.clone()));
+    }
+}
+
+
+/// The opening `w:ins` or `w:del` tag for `change`. If `empty`, the tag
+/// is self-closing, for marking a paragraph break.
+fn change_tag(change: &Change, id: usize, empty: bool) -> String {
+    let name = if change.kind == NodeKind::Deletion { "w:del" } else { "w:ins" };
+    let author = change.author.as_deref().unwrap_or(UNKNOWN_AUTHOR);
+    format!("<{} w:id=\"{}\" w:author=\"{}\"{}>", name, id, escape(author), if empty { "/" } else { "" })
+}
+
+
+fn run(text: &str, tag: &str) -> String {
+    format!("<w:r><{tag} xml:space=\"preserve\">{}</{tag}></w:r>", escape(text))
+}
+
+
+/// Read a Word document, with tracked insertions and deletions as 
+/// suggestions and Word comments as comments.
+///
+/// Authors' names become handles with spaces removed, so "Jane Doe"
+/// becomes `@JaneDoe`, and "Unknown" authors are dropped. Comments
+/// are placed where their range starts.
+/// Paragraphs are separated by blank lines.
+///
+/// # Errors
+///
+/// Returns an error if the file is not a valid Word document.
+pub fn read_docx<R: Read + Seek>(reader: R) -> Result<Node> {
+    let mut zip = ZipArchive::new(reader).context("Not a Word document")?;
+    let document = read_zip_file(&mut zip, "word/document.xml")?
+        .context("Not a Word document: no word/document.xml")?;
+    let comments = match read_zip_file(&mut zip, "word/comments.xml")? {
+        Some(xml) => read_comments(&xml)?,
+        None => HashMap::new()
+    };
+
+    let mut builder = Builder::new();
+    let mut changes: Vec<Change> = Vec::new();
+    let mut mark: Option<Change> = None;
+    let mut pending_break: Option<Option<Change>> = None;
+    let mut in_ppr = false;
+    let mut in_text = false;
+    let mut placed = HashSet::new();
+    let mut xml = Reader::from_str(&document);
+
+    loop {
+        let event = xml.read_event()?;
+        let is_start = matches!(event, Event::Start(_));
+        match event {
+            Event::Start(e) | Event::Empty(e) => {
+                let name = e.name();
+                match name.as_ref() {
+                    b"w:pPr" => in_ppr = is_start,
+                    b"w:ins" | b"w:del" | b"w:moveTo" | b"w:moveFrom" => {
+                        let change = change_from(&e)?;
+                        if in_ppr {
+                            mark = Some(change);
+                        } else if is_start {
+                            changes.push(change);
+                        }
+                    },
+                    b"w:t" | b"w:delText" => in_text = is_start,
+                    b"w:tab" | b"w:br" | b"w:cr" if ! in_ppr => {
+                        let text = if name.as_ref() == b"w:tab" { "\t" } else { "\n" };
+                        builder.push_break(&mut pending_break);
+                        builder.push_text(text, changes.last());
+                    },
+                    b"w:commentRangeStart" | b"w:commentReference" => {
+                        let id = attribute(&e, "w:id")?.unwrap_or_default();
+                        if let Some((author, text)) = comments.get(&id) {
+                            if placed.insert(id) {
+                                builder.push_break(&mut pending_break);
+                                builder.push_comment(text, author.as_deref());
+                            }
+                        }
+                    },
+                    _ => {}
+                }
+            },
+            Event::End(e) => match e.name().as_ref() {
+                b"w:pPr" => in_ppr = false,
+                b"w:ins" | b"w:del" | b"w:moveTo" | b"w:moveFrom" if ! in_ppr => { changes.pop(); },
+                b"w:t" | b"w:delText" => in_text = false,
+                b"w:p" => pending_break = Some(mark.take()),
+                _ => {}
+            },
+            Event::Text(t) if in_text => {
+                builder.push_break(&mut pending_break);
+                builder.push_text(&t.unescape()?, changes.last());
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(builder.root)
+}
+
+
+/// Read `word/comments.xml` into a map from comment ids to their
+/// author and text.
+fn read_comments(xml: &str) -> Result<HashMap<String, (Option<String>, String)>> {
+    let mut comments = HashMap::new();
+    let mut current: Option<(String, Option<String>, Vec<String>)> = None;
+    let mut in_text = false;
+    let mut reader = Reader::from_str(xml);
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) if e.name().as_ref() == b"w:comment" => {
+                let id = attribute(&e, "w:id")?.unwrap_or_default();
+                let author = handle_from(attribute(&e, "w:author")?);
+                current = Some((id, author, Vec::new()));
+            },
+            Event::Start(e) if e.name().as_ref() == b"w:p" => {
+                if let Some((_, _, paragraphs)) = current.as_mut() {
+                    paragraphs.push(String::new());
+                }
+            },
+            Event::Start(e) if e.name().as_ref() == b"w:t" => in_text = true,
+            Event::End(e) if e.name().as_ref() == b"w:t" => in_text = false,
+            Event::Text(t) if in_text => {
+                if let Some(paragraph) = current.as_mut().and_then(|(_, _, p)| p.last_mut()) {
+                    paragraph.push_str(&t.unescape()?);
+                }
+            },
+            Event::End(e) if e.name().as_ref() == b"w:comment" => {
+                if let Some((id, author, paragraphs)) = current.take() {
+                    comments.insert(id, (author, paragraphs.join("\n")));
+                }
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(comments)
+}
+
+
+fn change_from(e: &BytesStart) -> Result<Change> {
+    let kind = match e.name().as_ref() {
+        b"w:ins" | b"w:moveTo" => NodeKind::Addition,
+        _ => NodeKind::Deletion
+    };
+    let author = handle_from(attribute(e, "w:author")?);
+    Ok(Change { kind, author })
+}
+
+
+#[test]
+fn test_write_docx() {
+    use std::io::Read;
+
+    let node = crate::make_node_from_string(
+        "Tom & Jerry ++[<3 @alice]++.\n\nNew --[paragraph\n\n]--line%%[Why? @bob]%%.".to_string()
+    ).unwrap();
+    let mut buffer = std::io::Cursor::new(Vec::new());
+    write_docx(&node, &mut buffer).unwrap();
+
+    let mut zip = zip::ZipArchive::new(buffer).unwrap();
+    let mut document = String::new();
+    zip.by_name("word/document.xml").unwrap().read_to_string(&mut document).unwrap();
+    assert!(document.contains("<w:t xml:space=\"preserve\">Tom &amp; Jerry </w:t>"));
+    assert!(document.contains("<w:ins w:id=\"0\" w:author=\"@alice\"><w:r><w:t xml:space=\"preserve\">&lt;3</w:t></w:r></w:ins>"));
+    assert!(document.contains("<w:p><w:pPr><w:rPr><w:del w:id=\"1\" w:author=\"Unknown\"/></w:rPr></w:pPr>\
+        <w:r><w:t xml:space=\"preserve\">New </w:t></w:r><w:del w:id=\"2\" w:author=\"Unknown\"><w:r><w:delText"));
+    assert_eq!(document.matches("<w:p>").count(), 3);
+
+    let mut comments = String::new();
+    zip.by_name("word/comments.xml").unwrap().read_to_string(&mut comments).unwrap();
+    assert!(comments.contains("<w:comment w:id=\"0\" w:author=\"@bob\"><w:p><w:r><w:t xml:space=\"preserve\">Why?</w:t>"));
+}
+
+
+#[test]
+fn test_read_docx() {
+    let text = "Tom & Jerry ++[<3 @alice]++.\n\nNew --[paragraph @bob]--%%[Why? @bob]%%\n\n\
+        ++[Added paragraph.\n\n @alice]++The end.";
+    let node = crate::make_node_from_string(text.to_string()).unwrap();
+    let mut buffer = std::io::Cursor::new(Vec::new());
+    write_docx(&node, &mut buffer).unwrap();
+
+    let read = read_docx(buffer).unwrap();
+    assert_eq!(read.to_string_suggestion(), 
+        "Tom & Jerry ++[<3 @alice ]++.\n\nNew --[paragraph @bob ]--%%[Why? @bob]%%\n\n\
+        ++[Added paragraph.\n\n @alice ]++The end.");
+
+    // a document written by Word
+    let document = r#"<w:document xmlns:w="x"><w:body>
+        <w:p><w:pPr><w:rPr><w:ins w:id="9" w:author="Jane Doe" w:date="2024-01-01T00:00:00Z"/></w:rPr></w:pPr>
+        <w:r><w:t>It was </w:t></w:r><w:commentRangeStart w:id="3"/>
+        <w:del w:id="1" w:author="Jane Doe"><w:r><w:rPr><w:b/></w:rPr><w:delText>a</w:delText></w:r></w:del>
+        <w:ins w:id="2" w:author="Jane Doe"><w:r><w:t>the</w:t></w:r><w:r><w:tab/><w:t>best</w:t></w:r></w:ins>
+        <w:commentRangeEnd w:id="3"/><w:r><w:commentReference w:id="3"/></w:r></w:p>
+        <w:p><w:r><w:t xml:space="preserve"> of times.</w:t></w:r></w:p></w:body></w:document>"#;
+    let comments = r#"<w:comments xmlns:w="x"><w:comment w:id="3" w:author="Bob">
+        <w:p><w:r><w:t>Nice.</w:t></w:r></w:p></w:comment></w:comments>"#;
+    let mut buffer = std::io::Cursor::new(Vec::new());
+    let mut zip = ZipWriter::new(&mut buffer);
+    for (name, contents) in [("word/document.xml", document), ("word/comments.xml", comments)] {
+        zip.start_file(name, SimpleFileOptions::default()).unwrap();
+        zip.write_all(contents.as_bytes()).unwrap();
+    }
+    zip.finish().unwrap();
+
+    let read = read_docx(buffer).unwrap();
+    assert_eq!(read.to_string_suggestion(), 
+        "It was %%[Nice. @Bob]%%--[a @JaneDoe ]--++[the\tbest\n\n @JaneDoe ]++ of times.");
+}
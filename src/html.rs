@@ -0,0 +1,267 @@
+use crate::node::{Node, NodeKind, Chunk};
+
+use crate::xml::escape;
+
+use anyhow::Result;
+
+
+/// Colours for each author's changes, in order of their first change.
+const PALETTE: [&str; 8] = [
+    "#0969da", "#8250df", "#bf3989", "#bc4c00", "#1b7c83", "#9a6700", "#1a7f37", "#cf222e"
+];
+
+const CSS: &str = "body { font-family: Georgia, serif; line-height: 1.6; max-width: 40em; margin: 2em auto; padding: 0 18em 0 1em; }
+.suggestions { white-space: pre-wrap; }
+ins { --color: #1a7f37; color: var(--color); text-decoration: underline; }
+del { --color: #cf222e; color: var(--color); text-decoration: line-through; }
+aside { --color: #6e7781; }
+aside.comment { float: right; clear: right; width: 15em; margin-right: -17em; padding: 0.2em 0.6em;
+  border-left: 3px solid var(--color); background: #f6f8fa; color: #24292f;
+  font-family: sans-serif; font-size: 0.85em; line-height: 1.4; white-space: normal; }
+aside.comment .author { color: var(--color); font-weight: bold; }
+@media (max-width: 60em) {
+  body { padding-right: 1em; }
+  aside.comment { float: none; width: auto; margin: 0.5em 0; }
+}
+";
+
+const INTERACTIVE_CSS: &str = "body { padding-top: 3em; }
+.toolbar { position: fixed; top: 0; left: 0; right: 0; padding: 0.5em 1em; background: #f6f8fa;
+  border-bottom: 1px solid #d0d7de; font-family: sans-serif; font-size: 0.9em; }
+.controls { font-family: sans-serif; font-size: 0.7em; white-space: nowrap; user-select: none; }
+.controls button { padding: 0 0.3em; margin-left: 0.2em; cursor: pointer; }
+@media print { .toolbar, .controls { display: none; } }
+";
+
+const TOOLBAR: &str = "<div class=\"toolbar\"><span id=\"remaining\"></span>
+<button id=\"download\">Download suggestions file</button></div>
+";
+
+const SCRIPT: &str = r#"<script>
+const OPENERS = { INS: "++[", DEL: "--[", ASIDE: "%%[" };
+const CLOSERS = { INS: "]++", DEL: "]--", ASIDE: "]%%" };
+const container = document.querySelector(".suggestions");
+
+function isControl(node) {
+  return node.nodeType === Node.ELEMENT_NODE &&
+    (node.classList.contains("controls") || node.classList.contains("author"));
+}
+
+// Write the remaining text and suggestions back in suggestions format.
+function serialize(element) {
+  let text = "";
+  for (const child of element.childNodes) {
+    if (child.nodeType === Node.TEXT_NODE) {
+      text += child.data;
+    } else if (isControl(child)) {
+      continue;
+    } else if (child.tagName in OPENERS) {
+      text += OPENERS[child.tagName] + serialize(child) +
+        (child.dataset.authorString || "") + CLOSERS[child.tagName];
+    } else {
+      text += serialize(child);
+    }
+  }
+  return text;
+}
+
+function update() {
+  const count = container.querySelectorAll("ins, del, aside").length;
+  document.getElementById("remaining").textContent =
+    count + (count === 1 ? " suggestion" : " suggestions") + " left";
+}
+
+document.addEventListener("click", event => {
+  const button = event.target.closest(".controls button");
+  if (! button) return;
+  const change = button.parentElement.parentElement;
+  const accept = button.classList.contains("accept");
+  // keep the text of accepted additions and rejected deletions
+  if (change.tagName !== "ASIDE" && (change.tagName === "INS") === accept) {
+    for (const child of [...change.childNodes]) {
+      if (! isControl(child)) change.before(child);
+    }
+  }
+  change.remove();
+  container.normalize();
+  update();
+});
+
+document.getElementById("download").addEventListener("click", () => {
+  const link = document.createElement("a");
+  link.href = URL.createObjectURL(new Blob([serialize(container)], { type: "text/plain" }));
+  link.download = document.title;
+  link.click();
+  setTimeout(() => URL.revokeObjectURL(link.href), 1000);
+});
+
+update();
+</script>
+"#;
+
+
+impl Node {
+    /// Return a string representing the Node as an HTML fragment.
+    ///
+    /// Text is escaped. Additions become `<ins>`, deletions `<del>` and
+    /// comments `<aside class="comment">`. Every change has a
+    /// `data-author` attribute, empty if it is unsigned, and a `title`
+    /// like "Added by @alice". Each author's changes also get a class
+    /// `author-N`, which the stylesheet from
+    /// [`Self::to_string_html_document`] gives its own colour.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use suggestions::make_node_from_string;
+    /// let node = make_node_from_string("1 < 2 ++[& 3 @alice]++".to_string()).unwrap();
+    /// assert_eq!(node.to_string_html().unwrap(), "1 &lt; 2 <ins class=\"author-0\" \
+    ///     data-author=\"@alice\" title=\"Added by @alice\">&amp; 3</ins>");
+    /// ```
+    pub fn to_string_html(&self) -> Result<String> {
+        Ok(self.html_fragment(false))
+    }
+
+
+    /// Return a complete HTML document showing the Node, with an embedded
+    /// stylesheet. Comments are shown as notes in the margin.
+    pub fn to_string_html_document(&self, title: &str) -> Result<String> {
+        Ok(html_document(title, "", &self.html_fragment(false), ""))
+    }
+
+
+    /// Return a self-contained HTML page for reviewing the Node in a
+    /// browser. Each suggestion has buttons to accept or reject it, and
+    /// the page can download the result as a suggestions file, named
+    /// `title`, with any remaining suggestions kept.
+    pub fn to_string_html_interactive(&self, title: &str) -> Result<String> {
+        Ok(html_document(title, INTERACTIVE_CSS, &self.html_fragment(true), &format!("{}{}", TOOLBAR, SCRIPT)))
+    }
+
+
+    fn html_fragment(&self, interactive: bool) -> String {
+        let mut authors = Vec::new();
+        collect_authors(self, &mut authors);
+        let mut output = String::new();
+        self.push_html(&authors, interactive, &mut output);
+        output
+    }
+
+
+    /// Write the Node as HTML to `output`. If `interactive`, changes also
+    /// get their exact `author_string` and accept/reject buttons.
+    fn push_html(&self, authors: &[String], interactive: bool, output: &mut String) {
+        let author = self.author_clean();
+        let (tag, verb) = match self.kind {
+            NodeKind::Addition => ("ins", "Added"),
+            NodeKind::Deletion => ("del", "Deleted"),
+            NodeKind::Comment => ("aside", "Comment"),
+            NodeKind::Root => ("", "")
+        };
+        if ! tag.is_empty() {
+            let mut classes = Vec::new();
+            if self.kind == NodeKind::Comment {
+                classes.push("comment".to_string());
+            }
+            if let Some(i) = author.as_ref().and_then(|a| authors.iter().position(|b| b == a)) {
+                classes.push(format!("author-{}", i % PALETTE.len()));
+            }
+            let class = classes.join(" ");
+            let title = match &author {
+                Some(a) => format!("{} by {}", verb, a),
+                None => verb.to_string()
+            };
+            output.push_str(&format!("<{}", tag));
+            if ! class.is_empty() {
+                output.push_str(&format!(" class=\"{}\"", class));
+            }
+            output.push_str(&format!(" data-author=\"{}\" title=\"{}\"",
+                escape(author.as_deref().unwrap_or("")), escape(&title)));
+            if let (true, Some(author_string)) = (interactive, &self.author_string) {
+                output.push_str(&format!(" data-author-string=\"{}\"", escape(author_string)));
+            }
+            output.push('>');
+        }
+
+        for chunk in &self.contents {
+            match chunk {
+                Chunk::TextChunk(text) => output.push_str(&escape(text)),
+                Chunk::NodeChunk(nd) => nd.push_html(authors, interactive, output)
+            }
+        }
+
+        if let (NodeKind::Comment, Some(author)) = (self.kind, &author) {
+            output.push_str(&format!("<span class=\"author\"> {}</span>", escape(author)));
+        }
+        if interactive && ! tag.is_empty() {
+            output.push_str(if self.kind == NodeKind::Comment {
+                "<span class=\"controls\"><button class=\"reject\" title=\"Resolve\">&#x2715;</button></span>"
+            } else {
+                "<span class=\"controls\"><button class=\"accept\" title=\"Accept\">&#x2713;</button>\
+                    <button class=\"reject\" title=\"Reject\">&#x2715;</button></span>"
+            });
+        }
+        if ! tag.is_empty() {
+            output.push_str(&format!("</{}>", tag));
+        }
+    }
+}
+
+
+/// A complete HTML document with the standard stylesheet plus `css`.
+/// `script` goes at the end of the body.
+fn html_document(title: &str, css: &str, body: &str, script: &str) -> String {
+    let mut style = CSS.to_string();
+    for (i, color) in PALETTE.iter().enumerate() {
+        style.push_str(&format!(".author-{} {{ --color: {}; }}\n", i, color));
+    }
+    style.push_str(css);
+    format!("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+        <title>{}</title>\n<style>\n{}</style>\n</head>\n<body>\n\
+        <div class=\"suggestions\">{}</div>\n{}</body>\n</html>\n",
+        escape(title), style, body, script)
+}
+
+
+/// Add the authors in `node` to `authors`, in order of appearance.
+fn collect_authors(node: &Node, authors: &mut Vec<String>) {
+    if let Some(author) = node.author_clean() {
+        if ! authors.contains(&author) {
+            authors.push(author);
+        }
+    }
+    for chunk in &node.contents {
+        if let Chunk::NodeChunk(nd) = chunk {
+            collect_authors(nd, authors);
+        }
+    }
+}
+
+
+#[test]
+fn test_to_string_html() {
+    let node = crate::make_node_from_string(
+        "<b>Tom</b> & ++[Jerry --[\"Tuffy\" @bob]-- @alice]++%%[Hmm <3 @bob]%%--[gone]--".to_string()
+    ).unwrap();
+    assert_eq!(node.to_string_html().unwrap(),
+        "&lt;b&gt;Tom&lt;/b&gt; &amp; \
+        <ins class=\"author-0\" data-author=\"@alice\" title=\"Added by @alice\">Jerry \
+        <del class=\"author-1\" data-author=\"@bob\" title=\"Deleted by @bob\">&quot;Tuffy&quot;</del></ins>\
+        <aside class=\"comment author-1\" data-author=\"@bob\" title=\"Comment by @bob\">Hmm &lt;3\
+        <span class=\"author\"> @bob</span></aside>\
+        <del data-author=\"\" title=\"Deleted\">gone</del>");
+
+    let document = node.to_string_html_document("A & B").unwrap();
+    assert!(document.starts_with("<!DOCTYPE html>"));
+    assert!(document.contains("<title>A &amp; B</title>"));
+    assert!(document.contains(".author-1 { --color: #8250df; }"));
+    assert!(document.contains("<div class=\"suggestions\">&lt;b&gt;"));
+    assert!(! document.contains("<script>"));
+
+    let page = node.to_string_html_interactive("file.txt").unwrap();
+    assert!(page.contains("<ins class=\"author-0\" data-author=\"@alice\" title=\"Added by @alice\" \
+        data-author-string=\" @alice\">Jerry "));
+    assert!(page.contains("<span class=\"author\"> @bob</span><span class=\"controls\"><button class=\"reject\""));
+    assert_eq!(page.matches("<button class=\"accept\"").count(), 3);
+    assert!(page.contains("<script>"));
+}
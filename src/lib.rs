@@ -15,13 +15,27 @@
 
 
 mod node;
+mod diff;
+mod merge;
+mod overlap;
+mod patch;
+mod criticmarkup;
+mod xml;
+mod docx;
+mod odt;
+mod html;
 
 pub use node::{Node, NodeKind, Chunk};
+pub use diff::{DiffOptions, DiffAlgorithm, Granularity, WhitespaceMode, Layout};
+pub use diff::{diff_strings, diff_strings_with_warning, diff_readers, diff_onto_node, diff_onto_node_with_warning};
+pub use merge::{merge_suggestions, merge_three_way, rebase_suggestions, MergeConflict};
+pub use criticmarkup::make_node_from_criticmarkup;
+pub use docx::{write_docx, read_docx};
+pub use odt::{write_odt, read_odt};
+pub use overlap::{find_overlaps, Overlap, OverlapKind};
+pub use patch::{parse_patch, patch_to_suggestions, suggestions_to_patch, author_handle, Patch, Hunk};
 use node::*;
 
-use similar::{Algorithm, ChangeTag};
-use similar::utils::diff_words;
-
 use anyhow::{Result, bail};
 
 use std::fs::File;
@@ -62,8 +76,9 @@ pub fn make_node_from_string(mut text: String) -> Result<Node> {
         //       and the x "verbose" flag, to use whitespace.
         r"(?sx)
         (?<chunk_text> .*?)              # everything up to the author
-        (?<author_string> \ *@\S+?\s*)?  # optionally, an author tag (plus whitespace)
-                                         # note '\ ' matches a single literal space
+        (?<author_string> \ ?@\S+?\s*)?  # optionally, an author tag (plus whitespace)
+                                         # note '\ ' matches a single literal space,
+                                         # so other spaces stay in the text
         (?<tag>                     
             \+\+\[   |                   # either an opener...
             --\[     |
@@ -77,7 +92,7 @@ pub fn make_node_from_string(mut text: String) -> Result<Node> {
         ";
     let re = Regex::new(re_string).unwrap();
     
-    while ! text.is_empty() {        // read chunks up to the next marker (or EOF)
+    loop {                          // read chunks up to the next marker (or EOF)
         text = {
             let caps = re.captures(&text).unwrap(); 
             let mut chunk_text = caps["chunk_text"].to_string();
@@ -142,8 +157,6 @@ pub fn make_node_from_string(mut text: String) -> Result<Node> {
             remainder.to_string()
         }; 
     }
-
-    panic!("Couldn't parse changetxt, was it empty?")
 }
 
 
@@ -159,6 +172,8 @@ fn fix_newlines(mut remainder: String) -> String {
 
 /// Return the difference between two files in suggestions format.
 /// 
+/// See [`diff_strings`] to compare text that is already in memory.
+/// 
 /// # Examples
 /// 
 /// ```rust
@@ -173,50 +188,11 @@ pub fn make_suggestions_from_diff(
     path_new: &str, 
     author: Option<String>
 ) -> io::Result<String> {
-    let mut file_old = File::open(path_old)?;
-    let mut file_new = File::open(path_new)?;
-    let mut contents_old = String::new();
-    let mut contents_new = String::new();
-    file_old.read_to_string(&mut contents_old)?;
-    file_new.read_to_string(&mut contents_new)?;
-
-    let diffs = diff_words(Algorithm::Myers, &contents_old, &contents_new);
+    let file_old = File::open(path_old)?;
+    let file_new = File::open(path_new)?;
+    let opts = DiffOptions { author, ..Default::default() };
 
-    let nd = make_node_from_diffs(diffs, author);
+    let nd = diff_readers(file_old, file_new, &opts)?;
     let output = nd.to_string_suggestion();
     Ok(output) 
 }
-
-
-fn make_node_from_diffs(changes: Vec<(ChangeTag, &str)>, author: Option<String>) -> Node {
-    let author_string = author.map(|a| format!(" {} ", a));
-
-    let mut root = Node::root();
-    
-    for change in changes {
-        match change {
-            (ChangeTag::Equal, text) => {
-                root.contents.push(Chunk::TextChunk(text.to_string()));    
-            },
-            (ChangeTag::Insert, text) => {
-                let nd = Node {
-                    kind: NodeKind::Addition,
-                    contents: vec![Chunk::TextChunk(text.to_string())],
-                    author_string: author_string.clone()
-                };
-                root.contents.push(Chunk::NodeChunk(nd));
-            },
-            (ChangeTag::Delete, text) => {
-                let nd = Node {
-                    kind: NodeKind::Deletion,
-                    contents: vec![Chunk::TextChunk(text.to_string())],
-                    author_string: author_string.clone()
-                };
-                root.contents.push(Chunk::NodeChunk(nd));
-            }
-        };
-        
-    }
-
-    root
-}
\ No newline at end of file
@@ -55,11 +55,23 @@ TODO:
 
 */
 
+// Commands print their output with `Ok(println!(...))`.
+#![allow(clippy::unit_arg)]
+
 
 use suggestions::*;
-use clap::{Parser, Subcommand, Args};
-use anyhow::Result;
+use clap::{Parser, Subcommand, Args, ArgMatches, CommandFactory, FromArgMatches};
+use clap::parser::ValueSource;
+use colored::Colorize;
+use anyhow::{Result, Context, bail};
 use regex::Regex;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use rayon::prelude::*;
+use walkdir::WalkDir;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 
 #[derive(Parser)]
@@ -72,43 +84,358 @@ struct Cli {
 #[allow(clippy::upper_case_acronyms)]
 #[derive(Subcommand)]
 enum Commands {
-    /// Output diff from OLD to NEW in suggestions format
+    /// Output diff from OLD to NEW (and any later revisions) in suggestions format
     Diff(DiffArgs),
+    /// Edit the accepted text of FILE, then add your edits to FILE as suggestions
+    Suggest(SuggestArgs),
+    /// Combine two suggestions files, OURS and THEIRS, made from ORIGINAL
+    Merge {original: String, ours: String, theirs: String},
+    /// Move the suggestions in FILE onto NEW_BASE, an updated version of its original text
+    Rebase {file: String, new_base: String},
+    /// Apply the unified diff PATCH to its original file, and output the changes as suggestions
+    FromPatch(FromPatchArgs),
+    /// Output the changes in FILE as a unified diff from its old to its new version
+    ToPatch {
+        file: String,
+        /// Show LINES unchanged lines around each change
+        #[arg(short = 'U', long, value_name = "LINES", default_value_t = 3)]
+        context: usize,
+        /// List comments as '#' lines before the diff
+        #[arg(long)]
+        comments: bool
+    },
+    /// Show a file's changes as suggestions. Called by git as an external diff driver
+    GitDiffDriver(GitDiffDriverArgs),
+    /// Make `git diff` use suggs for text files in the current repository
+    GitInstallDriver {
+        /// Use the driver for files matching GLOB (can be repeated)
+        #[arg(long = "pattern", value_name = "GLOB", default_values = ["*.txt", "*.md", "*.tex"])]
+        patterns: Vec<String>
+    },
+    /// Merge suggestions files. Called by git as a merge driver with %O %A %B
+    MergeDriver {
+        /// The common ancestor's version
+        ancestor: String,
+        /// Our version. The merged file is written here
+        current: String,
+        /// The other branch's version
+        other: String
+    },
+    /// Show changes to FILE in its git history as suggestions, signed by each commit's author
+    Blame(BlameArgs),
+    /// Convert FILE between suggestions and other change-tracking formats
+    Convert {
+        /// Format of FILE
+        #[arg(long, value_enum, default_value_t = Format::Suggestions)]
+        from: Format,
+        /// Format to output
+        #[arg(long, value_enum, default_value_t = Format::Suggestions)]
+        to: Format,
+        file: String
+    },
+    /// Write suggestions FILE as a Word document with tracked changes
+    Docx {
+        file: String,
+        /// Path of the .docx file to write
+        #[arg(short, long, value_name = "DOCX")]
+        output: String
+    },
+    /// Read a Word document's tracked changes and comments, and output them as suggestions
+    FromDocx {file: String},
+    /// Write suggestions FILE as an OpenDocument text file with tracked changes
+    Odt {
+        file: String,
+        /// Path of the .odt file to write
+        #[arg(short, long, value_name = "ODT")]
+        output: String
+    },
+    /// Read an OpenDocument text file's tracked changes and annotations, and output them as suggestions
+    FromOdt {file: String},
+    /// List places in FILE where suggestions by different authors overlap
+    Conflicts {file: String},
     /// Output result of rejecting all changes in FILE
     Old {file: String},
     /// Output result of accepting all changes in FILE
     New {file: String},
-    /// Overwrite FILE, rejecting all changes
-    Reject {file: String},
-    /// Overwrite FILE, accepting all changes
-    Accept {file: String},
+    /// Overwrite FILES, rejecting all changes
+    Reject(WriteArgs),
+    /// Overwrite FILES, accepting all changes
+    Accept(WriteArgs),
     /// Print suggestions FILE, highlight changes and comments
     Colorize {file: String},
     /// Print suggestions FILE with TeX highlighting
     Tex {file: String}, 
     /// Print suggestions FILE with HTML highlighting
-    HTML {file: String},
+    HTML {
+        file: String,
+        /// Print a complete HTML document with a stylesheet, showing comments in the margin
+        #[arg(short, long)]
+        standalone: bool,
+        /// Print a self-contained web page where readers can accept or reject each suggestion, then download the result
+        #[arg(short, long)]
+        interactive: bool
+    },
 
     #[command(hide = true)]
     Trousers {},
 }
 
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum Format {
+    Suggestions,
+    #[value(name = "criticmarkup")]
+    CriticMarkup
+}
+
 #[derive(Args)]
 struct DiffArgs {
-    /// Add AUTHOR to diff
-    #[arg(short, long)]
-    author: Option<String>,
-    old: String, 
-    new: String 
+    #[command(flatten)]
+    diff: DiffOptionArgs,
+    /// Treat OLD as a suggestions file, and add changes to its accepted text
+    #[arg(long)]
+    base_suggestions: bool,
+    /// Read revisions from FILE: one per line, a path optionally followed by 
+    /// an author
+    #[arg(long, value_name = "FILE", conflicts_with = "files")]
+    manifest: Option<String>,
+    /// Compare FILE as it was at git revision REV with the working copy
+    #[arg(long, value_name = "REV", conflicts_with_all = ["manifest", "git_range"])]
+    git: Option<String>,
+    /// Compare FILE at git revision A with revision B, showing each commit
+    /// in between signed by its author
+    #[arg(long, value_name = "A..B", conflicts_with = "manifest")]
+    git_range: Option<String>,
+    /// OLD, then NEW. Give more files to show a chain of revisions. 
+    /// Each file's changes are signed by the last --author before it
+    #[arg(value_name = "FILES", required_unless_present = "manifest")]
+    files: Vec<String>
+}
+
+#[derive(Args)]
+struct SuggestArgs {
+    #[command(flatten)]
+    diff: DiffOptionArgs,
+    file: String
+}
+
+#[derive(Args)]
+struct FromPatchArgs {
+    #[command(flatten)]
+    diff: DiffOptionArgs,
+    /// Original file to apply PATCH to. Defaults to the path in the patch
+    #[arg(long, value_name = "FILE")]
+    source: Option<String>,
+    patch: String
+}
+
+#[derive(Args)]
+struct BlameArgs {
+    /// Show changes after git revision REV. Defaults to the whole history
+    #[arg(long, value_name = "REV")]
+    since: Option<String>,
+    /// Read author handles from FILE: one email and handle per line
+    #[arg(long, value_name = "FILE")]
+    authors: Option<String>,
+    file: String
+}
+
+#[derive(Args)]
+struct GitDiffDriverArgs {
+    #[command(flatten)]
+    diff: DiffOptionArgs,
+    /// Git's arguments: PATH OLD_FILE OLD_HEX OLD_MODE NEW_FILE NEW_HEX NEW_MODE
+    #[arg(value_name = "ARGS")]
+    args: Vec<String>
+}
+
+#[derive(Args)]
+struct DiffOptionArgs {
+    /// Add AUTHOR to changes
+    #[arg(short, long, env = "SUGGS_AUTHOR", action = clap::ArgAction::Append, value_parser = parse_author)]
+    author: Vec<String>,
+    /// Compare and report changes in units of this size
+    #[arg(short, long, value_enum, default_value_t = GranularityArg::Words)]
+    granularity: GranularityArg,
+    /// Diff algorithm to use
+    #[arg(long, value_enum, default_value_t = DiffAlgorithmArg::Myers)]
+    algorithm: DiffAlgorithmArg,
+    /// Give up after SECONDS and compare paragraphs instead
+    #[arg(long, value_name = "SECONDS", value_parser = parse_seconds)]
+    timeout: Option<Duration>,
+    /// Merge changes separated by at most WORDS unchanged words
+    #[arg(long, value_name = "WORDS", default_value_t = 1)]
+    coalesce: usize,
+    /// Report every change separately, without merging nearby changes
+    #[arg(long, conflicts_with = "coalesce")]
+    no_coalesce: bool,
+    /// Don't report changes that only differ in whitespace
+    #[arg(long)]
+    ignore_whitespace: bool,
+    /// Don't report rewrapped lines; paragraph breaks still count
+    #[arg(long)]
+    ignore_reflow: bool,
+    /// With --ignore-whitespace or --ignore-reflow, use the whitespace from this file
+    #[arg(long, value_enum, default_value_t = LayoutArg::New)]
+    layout: LayoutArg,
+}
+
+impl DiffOptionArgs {
+    fn diff_options(&self) -> DiffOptions {
+        DiffOptions {
+            author: self.author.last().cloned(),
+            granularity: self.granularity.into(),
+            algorithm: self.algorithm.into(),
+            timeout: self.timeout,
+            coalesce: (! self.no_coalesce).then_some(self.coalesce),
+            whitespace: if self.ignore_whitespace {
+                WhitespaceMode::Ignore
+            } else if self.ignore_reflow {
+                WhitespaceMode::IgnoreReflow
+            } else {
+                WhitespaceMode::Exact
+            },
+            layout: self.layout.into()
+        }
+    }
+}
+
+// Command line versions of the diff option enums, which keep clap out
+// of the library.
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum GranularityArg {
+    /// Individual characters: best for typo fixes
+    Chars,
+    /// Words and the whitespace between them
+    Words,
+    /// Sentences, ending in `.`, `!` or `?`, or at a paragraph break
+    Sentences,
+    /// Lines: best for code or data
+    Lines,
+    /// Paragraphs separated by blank lines
+    Paragraphs,
+}
+
+impl From<GranularityArg> for Granularity {
+    fn from(arg: GranularityArg) -> Granularity {
+        match arg {
+            GranularityArg::Chars => Granularity::Chars,
+            GranularityArg::Words => Granularity::Words,
+            GranularityArg::Sentences => Granularity::Sentences,
+            GranularityArg::Lines => Granularity::Lines,
+            GranularityArg::Paragraphs => Granularity::Paragraphs
+        }
+    }
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum DiffAlgorithmArg {
+    /// Myers' algorithm: fast and usually gives the shortest diff
+    Myers,
+    /// Patience diff: often more readable when text has moved around
+    Patience,
+    /// Longest common subsequence: slow, for small inputs
+    Lcs,
+}
+
+impl From<DiffAlgorithmArg> for DiffAlgorithm {
+    fn from(arg: DiffAlgorithmArg) -> DiffAlgorithm {
+        match arg {
+            DiffAlgorithmArg::Myers => DiffAlgorithm::Myers,
+            DiffAlgorithmArg::Patience => DiffAlgorithm::Patience,
+            DiffAlgorithmArg::Lcs => DiffAlgorithm::Lcs
+        }
+    }
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum LayoutArg {
+    Old,
+    New,
+}
+
+impl From<LayoutArg> for Layout {
+    fn from(arg: LayoutArg) -> Layout {
+        match arg {
+            LayoutArg::Old => Layout::Old,
+            LayoutArg::New => Layout::New
+        }
+    }
+}
+
+#[derive(Args)]
+struct WriteArgs {
+    /// Copy each file to its name plus SUFFIX (default "~") before overwriting it
+    #[arg(long, value_name = "SUFFIX", num_args = 0..=1, 
+          require_equals = true, default_missing_value = "~")]
+    backup: Option<String>,
+    /// Show the changes that would be made without writing any files
+    #[arg(long)]
+    dry_run: bool,
+    /// In directories, only process files matching GLOB (can be repeated)
+    #[arg(long, value_name = "GLOB")]
+    include: Vec<String>,
+    /// In directories, skip files matching GLOB (can be repeated)
+    #[arg(long, value_name = "GLOB")]
+    exclude: Vec<String>,
+    /// Files or directories. Directories are searched recursively.
+    #[arg(required = true)]
+    files: Vec<String>
 }
 
 
 fn main() -> Result<()> {
-    let cli = Cli::parse();
+    let matches = Cli::command().get_matches();
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
     
     match &cli.command {
-        Commands::Diff(DiffArgs{author, old, new}) => {
-            Ok(command_diff(old, new, author)?)
+        Commands::Diff(args) => {
+            command_diff(args, matches.subcommand_matches("diff").unwrap())
+        },
+        Commands::Suggest(args) => {
+            command_suggest(args)
+        },
+        Commands::Merge{original, ours, theirs} => {
+            command_merge(original, ours, theirs)
+        },
+        Commands::Rebase{file, new_base} => {
+            command_rebase(file, new_base)
+        },
+        Commands::FromPatch(args) => {
+            command_from_patch(args, matches.subcommand_matches("from-patch").unwrap())
+        },
+        Commands::ToPatch{file, context, comments} => {
+            command_to_patch(file, *context, *comments)
+        },
+        Commands::GitDiffDriver(args) => {
+            command_git_diff_driver(args)
+        },
+        Commands::GitInstallDriver{patterns} => {
+            command_git_install_driver(patterns)
+        },
+        Commands::MergeDriver{ancestor, current, other} => {
+            command_merge_driver(ancestor, current, other)
+        },
+        Commands::Blame(args) => {
+            command_blame(args)
+        },
+        Commands::Convert{from, to, file} => {
+            command_convert(*from, *to, file)
+        },
+        Commands::Docx{file, output} => {
+            command_docx(file, output)
+        },
+        Commands::FromDocx{file} => {
+            command_from_docx(file)
+        },
+        Commands::Odt{file, output} => {
+            command_odt(file, output)
+        },
+        Commands::FromOdt{file} => {
+            command_from_odt(file)
+        },
+        Commands::Conflicts{file} => {
+            command_conflicts(file)
         },
         Commands::Old{file} => {
             Ok(command_old(file)?)
@@ -116,11 +443,11 @@ fn main() -> Result<()> {
         Commands::New{file} => {
             command_new(file)
         },
-        Commands::Reject{file} => {
-            command_reject(file)
+        Commands::Reject(args) => {
+            command_reject(args)
         },
-        Commands::Accept{file} => {
-            command_accept(file)
+        Commands::Accept(args) => {
+            command_accept(args)
         },
         Commands::Colorize{file} => {
             command_colorize(file)
@@ -128,8 +455,8 @@ fn main() -> Result<()> {
         Commands::Tex{file} => {
             command_tex(file)
         },
-        Commands::HTML{file} => {
-            command_html(file)
+        Commands::HTML{file, standalone, interactive} => {
+            command_html(file, *standalone, *interactive)
         },
         Commands::Trousers{} => {
             command_trousers()
@@ -145,13 +472,535 @@ fn command_trousers() -> Result<()> {
 }
 
 
-fn command_diff(old: &str, new: &str, author: &Option<String>) -> Result<()> {
-    let author_canon = author.clone().map(|mut a| {
-        ensure_canonical_author(&mut a);
-        a
-    });
-    let result = make_suggestions_from_diff(old, new, author_canon)?;
-    Ok(println!("{}", result))
+fn command_diff(args: &DiffArgs, matches: &ArgMatches) -> Result<()> {
+    let revisions = if args.git.is_some() || args.git_range.is_some() {
+        git_revisions(args, matches)?
+    } else {
+        let paths = match &args.manifest {
+            Some(manifest) => read_manifest(manifest)?,
+            None => revisions_with_authors(&args.files, matches)
+        };
+        paths.into_iter().map(|(path, author)| {
+            let text = fs::read_to_string(&path)
+                .with_context(|| format!("Could not read '{}'", path))?;
+            Ok((text, author))
+        }).collect::<Result<Vec<_>>>()?
+    };
+    let node = diff_revisions(&revisions, args.base_suggestions, &args.diff.diff_options())?;
+    println!("{}", node.to_string_suggestion());
+    Ok(())
+}
+
+
+/// Show each of `revisions` as changes to the one before it. Each
+/// revision's author overrides the one in `default_opts`. If 
+/// `base_suggestions` is true, the first revision is read as suggestions.
+fn diff_revisions(
+    revisions: &[(String, Option<String>)], 
+    base_suggestions: bool, 
+    default_opts: &DiffOptions
+) -> Result<Node> {
+    let Some(((text_old, _), later)) = revisions.split_first() else {
+        bail!("No files given");
+    };
+    if later.is_empty() {
+        bail!("At least two revisions are needed for a diff");
+    }
+
+    let mut node = if base_suggestions {
+        make_node_from_string(text_old.clone())?
+    } else {
+        let mut root = Node::root();
+        root.contents.push(Chunk::TextChunk(text_old.clone()));
+        root
+    };
+    for (i, (text, author)) in later.iter().enumerate() {
+        let opts = DiffOptions {
+            author: author.clone().or(default_opts.author.clone()),
+            ..default_opts.clone()
+        };
+        node = if i == 0 && ! base_suggestions {
+            print_warning(diff_strings_with_warning(&node.to_string_accept(), text, &opts))
+        } else {
+            print_warning(diff_onto_node_with_warning(node, text, &opts))
+        };
+    }
+    Ok(node)
+}
+
+
+/// Print the warning from a diff, if there is one, and return its result.
+fn print_warning((node, warning): (Node, Option<String>)) -> Node {
+    if let Some(warning) = warning {
+        eprintln!("{}", warning);
+    }
+    node
+}
+
+
+/// Read the revisions of a single file from git, for `--git` or 
+/// `--git-range`. Returns each revision's text and author. Authors come
+/// from git, unless `--author` was given on the command line.
+fn git_revisions(args: &DiffArgs, matches: &ArgMatches) -> Result<Vec<(String, Option<String>)>> {
+    let [file] = args.files.as_slice() else {
+        bail!("--git and --git-range need exactly one FILE");
+    };
+    let use_git_authors = matches.value_source("author") != Some(ValueSource::CommandLine);
+    let git_author = |ident: String| use_git_authors.then(|| author_handle(&ident));
+
+    if let Some(rev) = &args.git {
+        let old = git_show(rev, file)?;
+        let new = fs::read_to_string(file)
+            .with_context(|| format!("Could not read '{}'", file))?;
+        let ident = git(file, &["var", "GIT_AUTHOR_IDENT"])?;
+        return Ok(vec![(old, None), (new, git_author(ident))]);
+    }
+
+    let range = args.git_range.as_ref().unwrap();
+    let Some((from, to)) = range.split_once("..") else {
+        bail!("Expected a git range like A..B, got '{}'", range);
+    };
+    let mut revisions = vec![(git_show(from, file)?, None)];
+    revisions.extend(git_history(file, range, git_author)?);
+    if revisions.len() == 1 {
+        // no commits touch the file, but show any other difference
+        revisions.push((git_show(to, file)?, None));
+    }
+    Ok(revisions)
+}
+
+
+/// The text of `path` after each commit to it in `range`, oldest first,
+/// with the author given by `author` for the commit author's identity.
+fn git_history(
+    path: &str, 
+    range: &str, 
+    author: impl Fn(String) -> Option<String>
+) -> Result<Vec<(String, Option<String>)>> {
+    let log = git(path, &["log", "--reverse", "--format=%H %an <%ae>", range, "--", &file_name(path)])?;
+    log.lines().map(|line| {
+        let (commit, ident) = line.split_once(' ').unwrap_or((line, ""));
+        Ok((git_show(commit, path)?, author(ident.to_string())))
+    }).collect()
+}
+
+
+fn command_blame(args: &BlameArgs) -> Result<()> {
+    let handles = match &args.authors {
+        Some(path) => read_authors(path)?,
+        None => Vec::new()
+    };
+    let handle_for = |ident: String| {
+        let email = ident.rsplit_once('<')
+            .map(|(_, e)| e.trim_end_matches('>').to_lowercase())
+            .unwrap_or_default();
+        let mapped = handles.iter().find(|(e, _)| *e == email).map(|(_, h)| h.clone());
+        Some(mapped.unwrap_or_else(|| author_handle(&ident)))
+    };
+
+    let (mut revisions, range) = match &args.since {
+        Some(rev) => (vec![(git_show(rev, &args.file)?, None)], format!("{}..HEAD", rev)),
+        None => (vec![(String::new(), None)], "HEAD".to_string())
+    };
+    revisions.extend(git_history(&args.file, &range, handle_for)?);
+    let current = fs::read_to_string(&args.file)
+        .with_context(|| format!("Could not read '{}'", args.file))?;
+    if current != revisions.last().unwrap().0 {
+        let ident = git(&args.file, &["var", "GIT_AUTHOR_IDENT"])?;
+        let ident = ident.rsplit_once('>').map_or(ident.as_str(), |(i, _)| i).to_string() + ">";
+        revisions.push((current, handle_for(ident)));
+    }
+    if revisions.len() == 1 {
+        // nothing has changed
+        revisions.push((revisions[0].0.clone(), None));
+    }
+
+    let opts = DiffOptions { coalesce: Some(1), ..Default::default() };
+    let node = diff_revisions(&revisions, false, &opts)?;
+    println!("{}", node.to_string_suggestion());
+    Ok(())
+}
+
+
+/// Read a table of email addresses and author handles. Each line holds an
+/// email and a handle. A name like "Jane Doe" becomes the handle
+/// `@JaneDoe`. Blank lines and lines starting with `#` are ignored.
+fn read_authors(path: &str) -> Result<Vec<(String, String)>> {
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("Could not read authors file '{}'", path))?;
+    let mut authors = Vec::new();
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((email, handle)) = line.split_once(char::is_whitespace) else {
+            bail!("Expected an email and a handle in '{}', got '{}'", path, line);
+        };
+        authors.push((email.to_lowercase(), author_handle(handle.trim())));
+    }
+    Ok(authors)
+}
+
+
+/// Act as a `GIT_EXTERNAL_DIFF` program. Git calls this with seven 
+/// arguments for a changed file, nine for a renamed or copied one, or
+/// just the path for an unmerged one.
+fn command_git_diff_driver(args: &GitDiffDriverArgs) -> Result<()> {
+    let (path, old_file, new_file, new_path) = match args.args.as_slice() {
+        [path, old_file, _, _, new_file, _, _] => (path, old_file, new_file, path),
+        [path, old_file, _, _, new_file, _, _, new_path, _] => (path, old_file, new_file, new_path),
+        [path] => {
+            println!("* Unmerged path {}", path);
+            return Ok(());
+        },
+        _ => bail!("Expected git's 7 or 9 external diff arguments, got {}", args.args.len())
+    };
+    // git sets this when it pipes our output to a pager that shows colors
+    if std::env::var_os("GIT_PAGER_IN_USE").is_some() {
+        colored::control::set_override(true);
+    }
+
+    let old = fs::read_to_string(old_file)
+        .with_context(|| format!("Could not read '{}'", old_file))?;
+    let new = fs::read_to_string(new_file)
+        .with_context(|| format!("Could not read '{}'", new_file))?;
+    if old == new {
+        return Ok(());
+    }
+    let node = print_warning(diff_strings_with_warning(&old, &new, &args.diff.diff_options()));
+    println!("{}", format!("diff --suggs a/{} b/{}", path, new_path).bold());
+    println!("{}", display_string(&node));
+    Ok(())
+}
+
+
+/// Set up `suggs git-diff-driver` as the diff driver for files matching
+/// `patterns`, in `.git/config` and `.gitattributes`.
+fn command_git_install_driver(patterns: &[String]) -> Result<()> {
+    let exe = std::env::current_exe().context("Could not find the suggs program")?;
+    let command = format!("\"{}\" git-diff-driver", exe.display());
+    git(".", &["config", "--local", "diff.suggs.command", &command])?;
+
+    let top = git(".", &["rev-parse", "--show-toplevel"])?;
+    let attributes_path = Path::new(top.trim()).join(".gitattributes");
+    let mut attributes = fs::read_to_string(&attributes_path).unwrap_or_default();
+    let existing: Vec<String> = attributes.lines().map(|l| l.trim().to_string()).collect();
+    for pattern in patterns {
+        let line = format!("{} diff=suggs", pattern);
+        if ! existing.contains(&line) {
+            if ! attributes.is_empty() && ! attributes.ends_with('\n') {
+                attributes.push('\n');
+            }
+            attributes.push_str(&line);
+            attributes.push('\n');
+        }
+    }
+    fs::write(&attributes_path, attributes)
+        .with_context(|| format!("Could not write '{}'", attributes_path.display()))?;
+    eprintln!("git diff will now show changes to {} as suggestions", patterns.join(", "));
+    Ok(())
+}
+
+
+/// Act as a git merge driver. The result is written to `current`. Returns
+/// an error, so that git marks the file as conflicted, if there were 
+/// conflicts.
+fn command_merge_driver(ancestor: &str, current: &str, other: &str) -> Result<()> {
+    let nodes = [ancestor, current, other].map(make_node_from_file);
+    let conflicts = match nodes {
+        [Ok(base), Ok(ours), Ok(theirs)] => {
+            let (node, conflicts) = merge_three_way(base, ours, theirs)?;
+            report_conflicts(&conflicts, "Conflicting changes");
+            print_suggestions_to_file(node.to_string_suggestion(), current, &None)?;
+            conflicts.len()
+        },
+        _ => {
+            eprintln!("Could not read all versions of '{}' as suggestions; using a line-by-line merge", current);
+            let status = std::process::Command::new("git")
+                .args(["merge-file", current, ancestor, other])
+                .status()
+                .context("Could not run git merge-file")?;
+            match status.code() {
+                Some(n) if n >= 0 => n as usize,
+                _ => bail!("git merge-file failed")
+            }
+        }
+    };
+    if conflicts > 0 {
+        bail!("{} conflicting changes in '{}'", conflicts, current);
+    }
+    Ok(())
+}
+
+
+/// Get the contents of `path` at git revision `rev`.
+fn git_show(rev: &str, path: &str) -> Result<String> {
+    git(path, &["show", &format!("{}:./{}", rev, file_name(path))])
+        .with_context(|| format!("Could not read '{}' at git revision '{}'", path, rev))
+}
+
+
+/// Run git with `args` in the directory containing `path`, and return its output.
+fn git(path: &str, args: &[&str]) -> Result<String> {
+    let dir = match Path::new(path).parent() {
+        Some(dir) if ! dir.as_os_str().is_empty() => dir,
+        _ => Path::new(".")
+    };
+    let output = std::process::Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .context("Could not run git")?;
+    if ! output.status.success() {
+        bail!("git {} failed: {}", args.join(" "), String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+
+fn file_name(path: &str) -> String {
+    Path::new(path).file_name()
+        .map(|f| f.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+
+/// Pair each of `files` with the last `--author` before it on the command
+/// line. Files before any `--author` get the first one.
+fn revisions_with_authors(files: &[String], matches: &ArgMatches) -> Vec<(String, Option<String>)> {
+    let authors: Vec<(usize, String)> = match (
+        matches.indices_of("author"), 
+        matches.get_many::<String>("author")
+    ) {
+        (Some(indices), Some(values)) => indices.zip(values.cloned()).collect(),
+        _ => Vec::new()
+    };
+    let file_indices = matches.indices_of("files").into_iter().flatten();
+
+    files.iter().zip(file_indices).map(|(file, file_index)| {
+        let author = authors.iter()
+            .rev()
+            .find(|(i, _)| *i < file_index)
+            .or(authors.first())
+            .map(|(_, a)| a.clone());
+        (file.clone(), author)
+    }).collect()
+}
+
+
+/// Read a manifest of revisions. Each line holds a path, relative to the 
+/// manifest, optionally followed by an author handle. Blank lines and
+/// lines starting with `#` are ignored.
+fn read_manifest(path: &str) -> Result<Vec<(String, Option<String>)>> {
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("Could not read manifest '{}'", path))?;
+    let dir = Path::new(path).parent().unwrap_or(Path::new(""));
+
+    let mut revisions = Vec::new();
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (file, author) = match line.rsplit_once(char::is_whitespace) {
+            Some((file, author)) if author.starts_with('@') => 
+                (file.trim_end(), Some(canonical_author(author)?)),
+            _ => (line, None)
+        };
+        revisions.push((dir.join(file).to_string_lossy().to_string(), author));
+    }
+    Ok(revisions)
+}
+
+
+/// Open the accepted text of FILE in the user's editor. Once it is 
+/// closed, add any edits to FILE as suggestions.
+fn command_suggest(args: &SuggestArgs) -> Result<()> {
+    let opts = args.diff.diff_options();
+    let node = make_node_from_file(&args.file)?;
+    let accepted = node.to_string_accept();
+
+    let tmp_path = std::env::temp_dir()
+        .join(format!("suggs-{}-{}", std::process::id(), file_name(&args.file)));
+    fs::write(&tmp_path, &accepted)?;
+    let edited = run_editor(&tmp_path).and_then(|_| Ok(fs::read_to_string(&tmp_path)?));
+    let _ = fs::remove_file(&tmp_path);
+    let edited = edited?;
+
+    if edited == accepted {
+        eprintln!("No changes made to '{}'", args.file);
+        return Ok(());
+    }
+    let node = print_warning(diff_onto_node_with_warning(node, &edited, &opts));
+    print_suggestions_to_file(node.to_string_suggestion(), &args.file, &None)
+}
+
+
+/// Run `$VISUAL` or `$EDITOR` (or `vi`) on `path` and wait for it to finish.
+fn run_editor(path: &Path) -> Result<()> {
+    let editor = ["VISUAL", "EDITOR"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|e| ! e.trim().is_empty())
+        .unwrap_or_else(|| "vi".to_string());
+    // allow e.g. EDITOR="code --wait"
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap();
+
+    let status = std::process::Command::new(program)
+        .args(words)
+        .arg(path)
+        .status()
+        .with_context(|| format!("Could not run editor '{}'", editor))?;
+    if ! status.success() {
+        bail!("Editor '{}' exited with {}", editor, status);
+    }
+    Ok(())
+}
+
+
+fn command_merge(original: &str, ours: &str, theirs: &str) -> Result<()> {
+    let text = fs::read_to_string(original)?;
+    let node_ours = make_node_from_file(ours)?;
+    let node_theirs = make_node_from_file(theirs)?;
+    let (node, conflicts) = merge_suggestions(&text, node_ours, node_theirs)
+        .with_context(|| format!("Could not merge '{}' and '{}'", ours, theirs))?;
+
+    report_conflicts(&conflicts, "Conflicting changes");
+    println!("{}", node.to_string_suggestion());
+    Ok(())
+}
+
+
+fn command_rebase(path: &str, new_base: &str) -> Result<()> {
+    let node = make_node_from_file(path)?;
+    let text = fs::read_to_string(new_base)?;
+    let (node, conflicts) = rebase_suggestions(node, &text);
+
+    report_conflicts(&conflicts, "Suggestion no longer applies");
+    println!("{}", node.to_string_suggestion());
+    Ok(())
+}
+
+
+fn command_from_patch(args: &FromPatchArgs, matches: &ArgMatches) -> Result<()> {
+    let text = fs::read_to_string(&args.patch)
+        .with_context(|| format!("Could not read '{}'", args.patch))?;
+    let patches = parse_patch(&text)
+        .with_context(|| format!("Could not read patch '{}'", args.patch))?;
+    let patch = match patches.as_slice() {
+        [patch] => patch,
+        [] => bail!("No changes found in '{}'", args.patch),
+        _ => bail!("'{}' changes {} files; suggs from-patch handles one file at a time", 
+                   args.patch, patches.len())
+    };
+
+    let source = match (&args.source, &patch.old_path) {
+        (Some(path), _) | (None, Some(path)) => fs::read_to_string(path)
+            .with_context(|| format!("Could not read '{}' (use --source to give the original file)", path))?,
+        (None, None) => String::new()
+    };
+    let mut opts = args.diff.diff_options();
+    // an author given on the command line beats the patch's author
+    if matches.value_source("author") != Some(ValueSource::CommandLine) {
+        if let Some(author) = &patch.author {
+            opts.author = Some(author_handle(author));
+        }
+    }
+
+    let node = patch_to_suggestions(&source, patch, &opts)?;
+    println!("{}", node.to_string_suggestion());
+    Ok(())
+}
+
+
+fn command_to_patch(path: &str, context: usize, comments: bool) -> Result<()> {
+    let node = make_node_from_file(path)?;
+    // the patch already ends in a newline
+    print!("{}", suggestions_to_patch(&node, path, context, comments));
+    Ok(())
+}
+
+
+fn command_convert(from: Format, to: Format, path: &str) -> Result<()> {
+    let node = match from {
+        Format::Suggestions => make_node_from_file(path)?,
+        Format::CriticMarkup => {
+            let text = fs::read_to_string(path)
+                .with_context(|| format!("Could not read '{}'", path))?;
+            make_node_from_criticmarkup(&text)?
+        }
+    };
+    let output = match to {
+        Format::Suggestions => node.to_string_suggestion(),
+        Format::CriticMarkup => node.to_string_criticmarkup()?
+    };
+    println!("{}", output);
+    Ok(())
+}
+
+
+fn command_docx(path: &str, output: &str) -> Result<()> {
+    let node = make_node_from_file(path)?;
+    let file = fs::File::create(output)
+        .with_context(|| format!("Could not create '{}'", output))?;
+    write_docx(&node, file)
+}
+
+
+fn command_from_docx(path: &str) -> Result<()> {
+    let file = fs::File::open(path)
+        .with_context(|| format!("Could not open '{}'", path))?;
+    let node = read_docx(file)
+        .with_context(|| format!("Could not read '{}'", path))?;
+    println!("{}", node.to_string_suggestion());
+    Ok(())
+}
+
+
+fn command_odt(path: &str, output: &str) -> Result<()> {
+    let node = make_node_from_file(path)?;
+    let file = fs::File::create(output)
+        .with_context(|| format!("Could not create '{}'", output))?;
+    write_odt(&node, file)
+}
+
+
+fn command_from_odt(path: &str) -> Result<()> {
+    let file = fs::File::open(path)
+        .with_context(|| format!("Could not open '{}'", path))?;
+    let node = read_odt(file)
+        .with_context(|| format!("Could not read '{}'", path))?;
+    println!("{}", node.to_string_suggestion());
+    Ok(())
+}
+
+
+fn command_conflicts(path: &str) -> Result<()> {
+    let node = make_node_from_file(path)?;
+    for overlap in find_overlaps(&node) {
+        let description = match overlap.kind {
+            OverlapKind::DeletionWrapsChange => "Deletion contains other changes",
+            OverlapKind::CompetingAdditions => "Competing additions",
+        };
+        println!("line {}: {} by {}: {:?}", overlap.line, description, 
+            overlap.authors.join(", "), overlap.text);
+    }
+    Ok(())
+}
+
+
+fn report_conflicts(conflicts: &[MergeConflict], message: &str) {
+    for conflict in conflicts {
+        let authors = if conflict.authors.is_empty() {
+            String::new()
+        } else {
+            format!(" by {}", conflict.authors.join(", "))
+        };
+        eprintln!("{}{} at line {}: {:?}", 
+            message, authors, conflict.line, conflict.original);
+    }
 }
 
 
@@ -169,6 +1018,17 @@ fn command_new(path: &str) -> Result<()> {
 }
 
 
+/// Show `node` in colour if the output supports it, or with its markup
+/// if not.
+fn display_string(node: &Node) -> String {
+    if colored::control::SHOULD_COLORIZE.should_colorize() {
+        node.to_colored_string().to_string()
+    } else {
+        node.to_string_suggestion()
+    }
+}
+
+
 fn command_colorize(path: &str) -> Result<()> {
     let node = make_node_from_file(path)?;
     let suggs = node.to_colored_string();
@@ -176,17 +1036,13 @@ fn command_colorize(path: &str) -> Result<()> {
 }
 
 
-fn command_reject(path: &str) -> Result<()> {
-    let node = make_node_from_file(path)?;
-    let suggs = node.to_string_reject();
-    print_suggestions_to_file(suggs, path)
+fn command_reject(args: &WriteArgs) -> Result<()> {
+    rewrite_files(args, Node::to_string_reject)
 }
 
 
-fn command_accept(path: &str) -> Result<()> {
-    let node = make_node_from_file(path)?;
-    let suggs = node.to_string_accept();
-    print_suggestions_to_file(suggs, path)
+fn command_accept(args: &WriteArgs) -> Result<()> {
+    rewrite_files(args, Node::to_string_accept)
 }
 
 
@@ -198,27 +1054,249 @@ fn command_tex(path: &str) -> Result<()> {
 }
 
 
-fn command_html(path: &str) -> Result<()> {
+fn command_html(path: &str, standalone: bool, interactive: bool) -> Result<()> {
     let node = make_node_from_file(path)?;
-    let html = node.to_string_html()?;
+    let html = if interactive {
+        node.to_string_html_interactive(&file_name(path))?
+    } else if standalone {
+        node.to_string_html_document(&file_name(path))?
+    } else {
+        node.to_string_html()?
+    };
 
     Ok(println!("{}", html))
 }
 
 
-fn ensure_canonical_author(author: &mut String) {
+fn parse_seconds(arg: &str) -> Result<Duration, String> {
+    let secs: f64 = arg.parse().map_err(|_| format!("'{}' is not a number", arg))?;
+    Duration::try_from_secs_f64(secs).map_err(|e| e.to_string())
+}
+
+
+fn parse_author(arg: &str) -> Result<String, String> {
+    canonical_author(arg).map_err(|e| e.to_string())
+}
+
+
+fn canonical_author(author: &str) -> Result<String> {
+    let mut author = author.to_string();
+    ensure_canonical_author(&mut author)?;
+    Ok(author)
+}
+
+
+fn ensure_canonical_author(author: &mut String) -> Result<()> {
     if ! author.starts_with('@') {
       author.insert(0, '@')
     }
     let re = Regex::new(r"^\S+$").unwrap();
     if ! re.is_match(author) {
-         panic!("Author '{}' contained space characters", author);
+        bail!("Author '{}' contains spaces. Use a handle like '{}'", author, author.split_whitespace().collect::<String>());
+    }
+    Ok(())
+} 
+
+
+/// What happened to a single file in [`rewrite_files`].
+enum Outcome {
+    Updated,
+    Unchanged,
+    Preview(String)
+}
+
+
+/// Rewrite every file given in `args` using `rewrite`, processing
+/// files in parallel. A single file behaves as it always has; for
+/// several files, a per-file report is printed at the end.
+fn rewrite_files(args: &WriteArgs, rewrite: fn(&Node) -> String) -> Result<()> {
+    let paths = collect_paths(&args.files, &args.include, &args.exclude)?;
+
+    if let [path] = paths.as_slice() {
+        if let Outcome::Preview(preview) = rewrite_file(path, rewrite, args)? {
+            println!("{}", preview);
+        }
+        return Ok(());
     }
- } 
+
+    let results: Vec<(&String, Result<Outcome>)> = paths
+        .par_iter()
+        .map(|path| (path, rewrite_file(path, rewrite, args)))
+        .collect();
+
+    let mut n_failed = 0;
+    for (path, result) in &results {
+        match result {
+            Ok(Outcome::Preview(preview)) => {
+                println!("==> {} <==", path);
+                println!("{}", preview);
+            },
+            Ok(Outcome::Updated) => eprintln!("updated:   {}", path),
+            Ok(Outcome::Unchanged) => eprintln!("unchanged: {}", path),
+            Err(e) => {
+                n_failed += 1;
+                eprintln!("failed:    {}: {:#}", path, e);
+            }
+        }
+    }
+
+    if n_failed > 0 {
+        bail!("{} of {} files could not be processed", n_failed, results.len());
+    }
+    Ok(())
+}
+
+
+fn rewrite_file(path: &str, rewrite: fn(&Node) -> String, args: &WriteArgs) -> Result<Outcome> {
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("Could not read '{}'", path))?;
+    let node = make_node_from_string(text.clone())?;
+    let string = rewrite(&node);
+
+    if args.dry_run {
+        Ok(Outcome::Preview(display_string(&preview_changes(&node, rewrite))))
+    } else if string == text {
+        Ok(Outcome::Unchanged)
+    } else {
+        print_suggestions_to_file(string, path, &args.backup)?;
+        Ok(Outcome::Updated)
+    }
+}
+
+
+/// Show how `rewrite` changes the file holding `node`: each top-level
+/// suggestion's markup is shown as a deletion, followed by its rewritten
+/// text as an addition.
+fn preview_changes(node: &Node, rewrite: fn(&Node) -> String) -> Node {
+    let mut preview = Node::root();
+    for chunk in &node.contents {
+        let nd = match chunk {
+            Chunk::NodeChunk(nd) => nd,
+            Chunk::TextChunk(text) => {
+                preview.contents.push(Chunk::TextChunk(text.clone()));
+                continue;
+            }
+        };
+        let (old, new) = (nd.to_string_suggestion(), rewrite(nd));
+        for (kind, text) in [(NodeKind::Deletion, old), (NodeKind::Addition, new)] {
+            if ! text.is_empty() {
+                preview.contents.push(Chunk::NodeChunk(Node {
+                    kind,
+                    contents: vec![Chunk::TextChunk(text)],
+                    author_string: None
+                }));
+            }
+        }
+    }
+    preview
+}
+
+
+/// Expand `paths` into a list of files. Directories are searched
+/// recursively, skipping hidden entries, and their files are filtered
+/// by the `include` and `exclude` globs, which are matched against paths
+/// relative to the directory. Paths to files are always kept.
+/// Returns an error if no files are found.
+fn collect_paths(
+    paths: &[String], 
+    include: &[String], 
+    exclude: &[String]
+) -> Result<Vec<String>> {
+    let include = build_globset(include)?;
+    let exclude = build_globset(exclude)?;
+    let mut files = Vec::new();
+
+    for path in paths {
+        if ! Path::new(path).is_dir() {
+            files.push(path.clone());
+            continue;
+        }
+        let walker = WalkDir::new(path)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_entry(|e| e.depth() == 0 || ! is_hidden(e.file_name()));
+        for entry in walker {
+            let entry = entry?;
+            if ! entry.file_type().is_file() {
+                continue;
+            }
+            let file = entry.path();
+            // globs are matched against the path inside the directory
+            let relative = file.strip_prefix(path).unwrap_or(file);
+            let included = match &include {
+                Some(g) => g.is_match(relative),
+                None => true
+            };
+            let excluded = exclude.as_ref().is_some_and(|g| g.is_match(relative));
+            if included && ! excluded {
+                files.push(file.to_string_lossy().to_string());
+            }
+        }
+    }
+
+    if files.is_empty() {
+        bail!("No files matched in '{}'", paths.join("', '"));
+    }
+    Ok(files)
+}
+
+
+fn build_globset(patterns: &[String]) -> Result<Option<GlobSet>> {
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern)?);
+    }
+    Ok(Some(builder.build()?))
+}
+
+
+fn is_hidden(name: &std::ffi::OsStr) -> bool {
+    name.to_string_lossy().starts_with('.')
+}
+
+
+/// Write `string` to `path` via a temporary file in the same directory,
+/// so that `path` is never left half-written. If `backup` is given, the
+/// old contents are first copied to `path` plus the backup suffix.
+fn print_suggestions_to_file(
+    string: String, 
+    path: &str, 
+    backup: &Option<String>
+) -> Result<()> {
+    let target = Path::new(path);
+    let tmp_path = temp_path_for(target);
+    fs::write(&tmp_path, string.as_str())
+        .with_context(|| format!("Could not write temporary file for '{}'", path))?;
+
+    let result = (|| -> Result<()> {
+        let permissions = fs::metadata(target)?.permissions();
+        fs::set_permissions(&tmp_path, permissions)?;
+        if let Some(suffix) = backup {
+            let backup_path = format!("{}{}", path, suffix);
+            fs::copy(target, &backup_path)
+                .with_context(|| format!("Could not back up '{}' to '{}'", path, backup_path))?;
+        }
+        fs::rename(&tmp_path, target)?;
+        Ok(())
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    result
+}
 
 
-fn print_suggestions_to_file(string: String, path: &str) -> Result<()> {
-    Ok(std::fs::write(path, string.as_str())?)
+fn temp_path_for(target: &Path) -> PathBuf {
+    let file_name = target
+        .file_name()
+        .map(|f| f.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let tmp_name = format!(".{}.suggs-{}.tmp", file_name, std::process::id());
+    target.with_file_name(tmp_name)
 }
 
 
@@ -264,19 +1342,18 @@ mod tests {
     #[test]
     fn test_ensure_canonical_author() {
         let mut x = "author".to_string();
-        ensure_canonical_author(&mut x);
+        ensure_canonical_author(&mut x).unwrap();
         assert_eq!(x, "@author");
 
         let mut y = "@author".to_string();
-        ensure_canonical_author(&mut y);
+        ensure_canonical_author(&mut y).unwrap();
         assert_eq!(y, "@author");
     }
 
     #[test]
-    #[should_panic]
     fn test_ensure_canonical_author_2() {
         let mut problematic = "@author with spaces".to_string();
-        ensure_canonical_author(&mut problematic);
+        assert!(ensure_canonical_author(&mut problematic).is_err());
     }
 }
 
@@ -0,0 +1,751 @@
+use crate::node::{Node, NodeKind, Chunk};
+use crate::diff::{DiffOptions, Segment, diff_segments};
+
+use anyhow::{Result, bail};
+use similar::TextDiff;
+
+use std::collections::HashSet;
+
+
+/// A place where two sets of suggestions overlap, found by 
+/// [`merge_suggestions`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct MergeConflict {
+    /// Byte offset where the conflict starts in the original text.
+    pub start: usize,
+    /// Byte offset where the conflict ends in the original text.
+    pub end: usize,
+    /// Line number of `start` in the original text, counting from 1.
+    pub line: usize,
+    /// The original text that both sets of suggestions changed.
+    pub original: String,
+    /// Authors who signed the conflicting suggestions, in order.
+    pub authors: Vec<String>,
+}
+
+
+/// A top-level suggestion, positioned by the original text it covers.
+struct Anchored {
+    start: usize,
+    end: usize,
+    source: usize,
+    /// Both sides made this suggestion.
+    shared: bool,
+    node: Node
+}
+
+
+impl Anchored {
+    /// The sides that made this suggestion.
+    fn sources(&self) -> HashSet<usize> {
+        if self.shared { HashSet::from([0, 1]) } else { HashSet::from([self.source]) }
+    }
+}
+
+
+/// Combine two suggestions files which were both made from `original`.
+/// 
+/// Suggestions that only one side made, or that both made identically,
+/// are kept as they are. Where the two sides changed overlapping text,
+/// or different authors inserted text at the same place, the original
+/// text is shown as a deletion followed by each side's version as a
+/// competing addition, and a [`MergeConflict`] is reported.
+/// 
+/// # Examples
+/// 
+/// ```rust
+/// # use suggestions::{make_node_from_string, merge_suggestions};
+/// let original = "The cat sat on the mat.";
+/// let alice = make_node_from_string(
+///     "The cat sat on the --[mat.]--++[rug. @alice]++".to_string()).unwrap();
+/// let bob = make_node_from_string(
+///     "The ++[black @bob ]++cat sat on the mat.".to_string()).unwrap();
+/// let (merged, conflicts) = merge_suggestions(original, alice, bob).unwrap();
+/// assert_eq!(merged.to_string_suggestion(), 
+///     "The ++[black @bob ]++cat sat on the --[mat.]--++[rug. @alice]++");
+/// assert!(conflicts.is_empty());
+/// ```
+/// 
+/// # Errors
+/// 
+/// Returns an error if either set of suggestions, when rejected,
+/// does not give back `original`.
+pub fn merge_suggestions(
+    original: &str, 
+    ours: Node, 
+    theirs: Node
+) -> Result<(Node, Vec<MergeConflict>)> {
+    if ours.to_string_reject() != original {
+        bail!("The first suggestions file was not made from the original text.");
+    }
+    if theirs.to_string_reject() != original {
+        bail!("The second suggestions file was not made from the original text.");
+    }
+
+    let mut items = anchor_suggestions(ours, 0);
+    let theirs = anchor_suggestions(theirs, 1);
+    let theirs_seen: HashSet<(usize, usize, String)> = theirs.iter()
+        .map(|a| (a.start, a.end, a.node.to_string_suggestion()))
+        .collect();
+    for item in items.iter_mut() {
+        item.shared = theirs_seen.contains(&(item.start, item.end, item.node.to_string_suggestion()));
+    }
+    let ours_seen: HashSet<(usize, usize, String)> = items.iter()
+        .map(|a| (a.start, a.end, a.node.to_string_suggestion()))
+        .collect();
+    items.extend(theirs.into_iter().filter(|a| 
+        ! ours_seen.contains(&(a.start, a.end, a.node.to_string_suggestion()))
+    ));
+    // Stable, so each side's suggestions stay in order. At the same
+    // place, insertions come before deletions.
+    items.sort_by_key(|a| (a.start, a.end > a.start, a.source));
+
+    let mut root = Node::root();
+    let mut conflicts = Vec::new();
+    let mut pos = 0;
+    let mut items = items.into_iter().peekable();
+
+    while let Some(item) = items.next() {
+        push_text(&mut root, &original[pos..item.start]);
+        // Gather everything overlapping `item`. Insertions strictly inside
+        // a deletion overlap it, as do insertions right after a deletion
+        // by the same side, which replace the deleted text. Insertions at
+        // the same place overlap each other.
+        let (start, mut end) = (item.start, item.end);
+        let mut ends_here = item.sources();
+        let mut cluster = vec![item];
+        let mut comments = Vec::new();
+        while let Some(next) = items.next_if(|a| if a.end > a.start {
+            a.start < end
+        } else if start == end {
+            a.start == start
+        } else {
+            a.start > start && (a.start < end || a.start == end && ! ends_here.is_disjoint(&a.sources()))
+        }) {
+            if next.end > end {
+                end = next.end;
+                ends_here = next.sources();
+            } else if next.end == end && next.end > next.start {
+                ends_here.extend(next.sources());
+            }
+            if next.node.kind == NodeKind::Comment {
+                comments.push(next);
+            } else {
+                cluster.push(next);
+            }
+        }
+
+        for comment in comments {
+            root.contents.push(Chunk::NodeChunk(comment.node));
+        }
+        let sources: HashSet<usize> = cluster.iter()
+            .filter(|a| ! a.shared)
+            .map(|a| a.source)
+            .collect();
+        // Insertions at the same place only compete if different authors
+        // made them. Unsigned insertions are by each side's own reviewer.
+        let mut insertion_authors: Vec<Result<String, usize>> = cluster.iter()
+            .filter(|a| ! a.shared)
+            .map(|a| a.node.author_clean().ok_or(a.source))
+            .collect();
+        insertion_authors.sort();
+        insertion_authors.dedup();
+        if sources.len() < 2 || start == end && insertion_authors.len() < 2 {
+            for item in cluster {
+                root.contents.push(Chunk::NodeChunk(item.node));
+            }
+        } else {
+            let mut authors: Vec<String> = cluster.iter()
+                .filter_map(|a| a.node.author_clean())
+                .collect();
+            authors.sort();
+            authors.dedup();
+            conflicts.push(MergeConflict {
+                start,
+                end,
+                line: original[..start].matches('\n').count() + 1,
+                original: original[start..end].to_string(),
+                authors
+            });
+            push_alternatives(&mut root, original, start, end, cluster);
+        }
+        pos = end;
+    }
+    push_text(&mut root, &original[pos..]);
+
+    Ok((root, conflicts))
+}
+
+
+/// Move suggestions onto an updated version of the text they were made from.
+/// 
+/// Each top-level suggestion in `suggestions` is relocated to the matching
+/// place in `new_base`, found by diffing the old and new base texts.
+/// Suggestions whose text was changed in `new_base` no longer apply. They
+/// are replaced by a comment describing them, and reported as conflicts
+/// with positions in the old base text.
+/// 
+/// # Examples
+/// 
+/// ```rust
+/// # use suggestions::{make_node_from_string, rebase_suggestions};
+/// let suggestions = make_node_from_string(
+///     "The cat sat on the --[mat.]--++[rug. @alice]++".to_string()).unwrap();
+/// let (rebased, conflicts) = rebase_suggestions(suggestions, "Then the cat sat on the mat.");
+/// assert_eq!(rebased.to_string_suggestion(), 
+///     "Then the cat sat on the --[mat.]--++[rug. @alice]++");
+/// assert!(conflicts.is_empty());
+/// ```
+pub fn rebase_suggestions(suggestions: Node, new_base: &str) -> (Node, Vec<MergeConflict>) {
+    let old_base = suggestions.to_string_reject();
+    let (segments, _) = diff_segments(&old_base, new_base, &DiffOptions::default());
+    let mut moves = Vec::new();
+    let (mut old_pos, mut new_pos) = (0, 0);
+    for segment in segments {
+        match segment {
+            Segment::Same(same) => {
+                moves.push(Move { old_start: old_pos, new_start: new_pos, len: same.len() });
+                old_pos += same.len();
+                new_pos += same.len();
+            },
+            Segment::Changed(del, ins) => {
+                old_pos += del.len();
+                new_pos += ins.len();
+            }
+        }
+    }
+
+    let mut root = Node::root();
+    let mut conflicts = Vec::new();
+    let mut pos = 0;
+    let mut items = anchor_suggestions(suggestions, 0).into_iter().peekable();
+    while let Some(item) = items.next() {
+        // A deletion followed by an addition from the same author
+        // replaces the deleted text, so they move or fail together.
+        let addition = items.next_if(|a| item.node.kind == NodeKind::Deletion 
+            && a.node.kind == NodeKind::Addition
+            && a.start == item.end
+            && a.node.author_clean() == item.node.author_clean());
+        let target = find_move(&moves, item.start, item.end)
+            .map(|m| (m.map(item.start), m.map(item.end)));
+        let applies = target.is_some() || item.node.kind == NodeKind::Comment;
+        let (start, end) = target.unwrap_or_else(|| {
+            let floor = map_floor(&moves, item.start).max(pos);
+            (floor, floor)
+        });
+        push_text(&mut root, &new_base[pos..start]);
+        if applies {
+            root.contents.push(Chunk::NodeChunk(item.node));
+            root.contents.extend(addition.map(|a| Chunk::NodeChunk(a.node)));
+        } else {
+            conflicts.push(MergeConflict {
+                start: item.start,
+                end: item.end,
+                line: old_base[..item.start].matches('\n').count() + 1,
+                original: old_base[item.start..item.end].to_string(),
+                authors: item.node.author_clean().into_iter().collect()
+            });
+            let lost = match addition {
+                Some(addition) => describe_lost_replacement(item.node, addition.node),
+                None => describe_lost_suggestion(item.node)
+            };
+            root.contents.push(Chunk::NodeChunk(lost));
+        }
+        pos = end;
+    }
+    push_text(&mut root, &new_base[pos..]);
+
+    (root, conflicts)
+}
+
+
+/// Three-way merge of two suggestions files, `ours` and `theirs`, which
+/// both started as `base`.
+/// 
+/// Suggestions from `base` which one side has accepted or rejected are
+/// accepted or rejected on the other side too. The two sides' changes to
+/// the underlying text are then merged, like `git merge-file` does, and
+/// each side's suggestions are moved onto the merged text with
+/// [`rebase_suggestions`] and combined with [`merge_suggestions`]. Where
+/// both sides changed the same text differently, `base`'s text is shown
+/// as an unsigned deletion followed by each side's version as an unsigned
+/// addition, and a [`MergeConflict`] is reported.
+/// 
+/// # Examples
+/// 
+/// ```rust
+/// # use suggestions::{make_node_from_string, merge_three_way};
+/// let base = make_node_from_string("The cat sat on the ++[red @a ]++mat.".to_string()).unwrap();
+/// let ours = make_node_from_string("The cat sat on the red mat.".to_string()).unwrap();
+/// let theirs = make_node_from_string(
+///     "The --[cat]--++[dog @b]++ sat on the ++[red @a ]++mat.".to_string()).unwrap();
+/// let (merged, conflicts) = merge_three_way(base, ours, theirs).unwrap();
+/// assert_eq!(merged.to_string_suggestion(), "The --[cat]--++[dog @b]++ sat on the red mat.");
+/// assert!(conflicts.is_empty());
+/// ```
+pub fn merge_three_way(
+    base: Node, 
+    mut ours: Node, 
+    mut theirs: Node
+) -> Result<(Node, Vec<MergeConflict>)> {
+    let resolved_by_ours = resolved_suggestions(&base, &ours);
+    let resolved_by_theirs = resolved_suggestions(&base, &theirs);
+    resolve_suggestions(&mut theirs, &resolved_by_ours, &ours.to_string_reject());
+    resolve_suggestions(&mut ours, &resolved_by_theirs, &theirs.to_string_reject());
+
+    let text_base = base.to_string_reject();
+    let text_ours = ours.to_string_reject();
+    let text_theirs = theirs.to_string_reject();
+
+    if text_ours == text_theirs {
+        merge_suggestions(&text_ours, ours, theirs)
+    } else if text_theirs == text_base {
+        let (theirs, mut conflicts) = rebase_suggestions(theirs, &text_ours);
+        let (merged, more) = merge_suggestions(&text_ours, ours, theirs)?;
+        conflicts.extend(more);
+        Ok((merged, conflicts))
+    } else if text_ours == text_base {
+        let (ours, mut conflicts) = rebase_suggestions(ours, &text_theirs);
+        let (merged, more) = merge_suggestions(&text_theirs, ours, theirs)?;
+        conflicts.extend(more);
+        Ok((merged, conflicts))
+    } else {
+        let (text, alternatives, mut conflicts) = merge_text(&text_base, &text_ours, &text_theirs);
+        let (ours, lost) = rebase_suggestions(ours, &text);
+        conflicts.extend(lost);
+        let (theirs, lost) = rebase_suggestions(theirs, &text);
+        conflicts.extend(lost);
+        let (merged, more) = merge_suggestions(&text, ours, theirs)?;
+        conflicts.extend(more);
+        let (merged, more) = merge_suggestions(&text, alternatives, merged)?;
+        conflicts.extend(more);
+        Ok((merged, conflicts))
+    }
+}
+
+
+/// A change to a text: `original[start..end]` is replaced by `text`.
+#[derive(Clone, PartialEq)]
+struct Edit {
+    start: usize,
+    end: usize,
+    text: String
+}
+
+
+/// The changes that turn `original` into `changed`.
+fn text_edits(original: &str, changed: &str) -> Vec<Edit> {
+    let mut edits = Vec::new();
+    let mut pos = 0;
+    for segment in diff_segments(original, changed, &DiffOptions::default()).0 {
+        match segment {
+            Segment::Same(same) => pos += same.len(),
+            Segment::Changed(del, ins) => {
+                edits.push(Edit { start: pos, end: pos + del.len(), text: ins });
+                pos += del.len();
+            }
+        }
+    }
+    edits
+}
+
+
+/// `original[start..end]` with those of `edits` that fall in it applied.
+fn apply_edits(original: &str, start: usize, end: usize, edits: &[&Edit]) -> String {
+    let mut text = String::new();
+    let mut pos = start;
+    for edit in edits {
+        text.push_str(&original[pos..edit.start]);
+        text.push_str(&edit.text);
+        pos = edit.end;
+    }
+    text.push_str(&original[pos..end]);
+    text
+}
+
+
+/// Merge the changes that turn `base` into `ours` and into `theirs`.
+/// 
+/// Returns the merged text, in which changes that overlap and differ 
+/// are left out. Suggestions on the merged text show each such conflict
+/// as a deletion of `base`'s text followed by each side's version as an
+/// addition. The conflicts are also returned, with positions in the
+/// merged text.
+fn merge_text(base: &str, ours: &str, theirs: &str) -> (String, Node, Vec<MergeConflict>) {
+    let mut edits: Vec<(usize, Edit)> = text_edits(base, ours).into_iter().map(|e| (0, e))
+        .chain(text_edits(base, theirs).into_iter().map(|e| (1, e)))
+        .collect();
+    edits.sort_by_key(|(source, e)| (e.start, e.end, *source));
+
+    let mut text = String::new();
+    let mut alternatives = Node::root();
+    let mut conflicts = Vec::new();
+    let mut pos = 0;
+    let mut edits = edits.into_iter().peekable();
+
+    while let Some((source, edit)) = edits.next() {
+        // Edits overlap if they change the same text or insert at the
+        // same place.
+        let (start, mut end) = (edit.start, edit.end);
+        let mut cluster = vec![(source, edit)];
+        while let Some(next) = edits.next_if(|(_, e)| e.start < end || e.start == start) {
+            end = end.max(next.1.end);
+            cluster.push(next);
+        }
+
+        let versions: Vec<String> = (0..2).map(|side| {
+            let side_edits: Vec<&Edit> = cluster.iter()
+                .filter(|(s, _)| *s == side)
+                .map(|(_, e)| e)
+                .collect();
+            apply_edits(base, start, end, &side_edits)
+        }).collect();
+        let one_sided = cluster.iter().all(|(s, _)| *s == cluster[0].0);
+
+        let unchanged = &base[pos..start];
+        text.push_str(unchanged);
+        push_text(&mut alternatives, unchanged);
+        if one_sided || versions[0] == versions[1] {
+            let version = &versions[cluster[0].0];
+            text.push_str(version);
+            push_text(&mut alternatives, version);
+        } else {
+            let original = &base[start..end];
+            conflicts.push(MergeConflict {
+                start: text.len(),
+                end: text.len() + original.len(),
+                line: text.matches('\n').count() + 1,
+                original: original.to_string(),
+                authors: Vec::new()
+            });
+            text.push_str(original);
+            for (kind, version) in [(NodeKind::Deletion, original), 
+                    (NodeKind::Addition, &versions[0]), (NodeKind::Addition, &versions[1])] {
+                if ! version.is_empty() {
+                    alternatives.contents.push(Chunk::NodeChunk(Node {
+                        kind,
+                        contents: vec![Chunk::TextChunk(version.to_string())],
+                        author_string: None
+                    }));
+                }
+            }
+        }
+        pos = end;
+    }
+    text.push_str(&base[pos..]);
+    push_text(&mut alternatives, &base[pos..]);
+
+    (text, alternatives, conflicts)
+}
+
+
+/// The top-level suggestions in `base` which are missing from `side`,
+/// because `side` accepted or rejected them.
+fn resolved_suggestions(base: &Node, side: &Node) -> Vec<String> {
+    let mut remaining = top_level_suggestions(side);
+    top_level_suggestions(base).into_iter().filter(|s| {
+        match remaining.iter().position(|r| r == s) {
+            Some(i) => { remaining.remove(i); false },
+            None => true
+        }
+    }).collect()
+}
+
+
+fn top_level_suggestions(node: &Node) -> Vec<String> {
+    node.contents.iter().filter_map(|chunk| match chunk {
+        Chunk::NodeChunk(nd) => Some(nd.to_string_suggestion()),
+        Chunk::TextChunk(_) => None
+    }).collect()
+}
+
+
+/// Accept or reject each of the top-level `suggestions` in `side`, 
+/// whichever brings its original text closer to `target`.
+fn resolve_suggestions(side: &mut Node, suggestions: &[String], target: &str) {
+    for suggestion in suggestions {
+        let Some(i) = side.contents.iter().position(|chunk| 
+            matches!(chunk, Chunk::NodeChunk(nd) if nd.to_string_suggestion() == *suggestion)
+        ) else {
+            continue;
+        };
+        let Chunk::NodeChunk(nd) = &side.contents[i] else { unreachable!() };
+        let (accepted, rejected) = (nd.to_string_accept(), nd.to_string_reject());
+
+        side.contents[i] = Chunk::TextChunk(accepted.clone());
+        let accept_ratio = TextDiff::from_words(side.to_string_reject().as_str(), target).ratio();
+        side.contents[i] = Chunk::TextChunk(rejected);
+        let reject_ratio = TextDiff::from_words(side.to_string_reject().as_str(), target).ratio();
+        if accept_ratio > reject_ratio {
+            side.contents[i] = Chunk::TextChunk(accepted);
+        }
+    }
+}
+
+
+/// A stretch of unchanged text, which has moved from `old_start` in
+/// the old text to `new_start` in the new text.
+struct Move {
+    old_start: usize,
+    new_start: usize,
+    len: usize
+}
+
+
+impl Move {
+    fn map(&self, old_pos: usize) -> usize {
+        old_pos - self.old_start + self.new_start
+    }
+}
+
+
+/// The first unchanged stretch that contains all of `start..end`.
+fn find_move(moves: &[Move], start: usize, end: usize) -> Option<&Move> {
+    moves.iter().find(|m| m.old_start <= start && end <= m.old_start + m.len)
+}
+
+
+/// Where `old_pos` ended up in the new text, or if it was changed, 
+/// the end of the last unchanged text before it.
+fn map_floor(moves: &[Move], old_pos: usize) -> usize {
+    if let Some(m) = find_move(moves, old_pos, old_pos) {
+        return m.map(old_pos);
+    }
+    moves.iter()
+        .rev()
+        .find(|m| m.old_start + m.len <= old_pos)
+        .map_or(0, |m| m.new_start + m.len)
+}
+
+
+/// Make a comment describing a suggestion that could not be applied.
+fn describe_lost_suggestion(node: Node) -> Node {
+    let description = match node.kind {
+        NodeKind::Addition => format!("Could not apply addition of {:?}", node.to_string_accept()),
+        NodeKind::Deletion => format!("Could not apply deletion of {:?}", node.to_string_reject()),
+        _ => format!("Could not apply {:?}", node.to_string_suggestion())
+    };
+    Node {
+        kind: NodeKind::Comment,
+        contents: vec![Chunk::TextChunk(description)],
+        author_string: node.author_string
+    }
+}
+
+
+/// Make a comment describing a replacement, a `deletion` followed by
+/// an `addition`, that could not be applied.
+fn describe_lost_replacement(deletion: Node, addition: Node) -> Node {
+    let description = format!("Could not apply replacement of {:?} with {:?}", 
+        deletion.to_string_reject(), addition.to_string_accept());
+    Node {
+        kind: NodeKind::Comment,
+        contents: vec![Chunk::TextChunk(description)],
+        author_string: deletion.author_string
+    }
+}
+
+
+/// Take the top-level suggestions out of `node`, noting which
+/// range of the original (rejected) text each covers.
+fn anchor_suggestions(node: Node, source: usize) -> Vec<Anchored> {
+    let mut anchored = Vec::new();
+    let mut pos = 0;
+    for chunk in node.contents {
+        match chunk {
+            Chunk::TextChunk(text) => pos += text.len(),
+            Chunk::NodeChunk(nd) => {
+                let start = pos;
+                pos += nd.to_string_reject().len();
+                anchored.push(Anchored { start, end: pos, source, shared: false, node: nd });
+            }
+        }
+    }
+    anchored
+}
+
+
+/// Show `original[start..end]` as a deletion, followed by each
+/// side's version of it as an addition. An addition is signed if one
+/// author made all of that side's own suggestions.
+fn push_alternatives(
+    root: &mut Node, 
+    original: &str, 
+    start: usize, 
+    end: usize, 
+    cluster: Vec<Anchored>
+) {
+    if end > start {
+        root.contents.push(Chunk::NodeChunk(Node {
+            kind: NodeKind::Deletion,
+            contents: vec![Chunk::TextChunk(original[start..end].to_string())],
+            author_string: None
+        }));
+    }
+
+    for source in 0..2 {
+        let mut version = String::new();
+        let mut authors = Vec::new();
+        let mut pos = start;
+        for item in cluster.iter().filter(|a| a.source == source || a.shared) {
+            version.push_str(&original[pos..item.start]);
+            version.push_str(&item.node.to_string_accept());
+            if ! item.shared {
+                authors.extend(item.node.author_clean());
+            }
+            pos = item.end;
+        }
+        version.push_str(&original[pos..end]);
+        if version.is_empty() {
+            continue;
+        }
+        authors.sort();
+        authors.dedup();
+        let author_string = match authors.as_slice() {
+            [author] => Some(format!(" {} ", author)),
+            _ => None
+        };
+        root.contents.push(Chunk::NodeChunk(Node {
+            kind: NodeKind::Addition,
+            contents: vec![Chunk::TextChunk(version)],
+            author_string
+        }));
+    }
+}
+
+
+fn push_text(root: &mut Node, text: &str) {
+    if ! text.is_empty() {
+        root.contents.push(Chunk::TextChunk(text.to_string()));
+    }
+}
+
+
+#[test]
+fn test_merge_conflicts() {
+    use crate::make_node_from_string;
+
+    let original = "One two three four.\nFive six.";
+    let ours = make_node_from_string(
+        "One --[two three @a]--++[2 3 @a]++ four.\nFive six.%%[Good. @a]%%".to_string()
+    ).unwrap();
+    let theirs = make_node_from_string(
+        "One two --[three four. @b]--++[3 4. @b]++\nFive six.%%[Good. @a]%%".to_string()
+    ).unwrap();
+    let (merged, conflicts) = merge_suggestions(original, ours, theirs).unwrap();
+
+    assert_eq!(merged.to_string_suggestion(), 
+        "One --[two three four.]--++[2 3 four. @a ]++++[two 3 4. @b ]++\nFive six.%%[Good. @a]%%");
+    assert_eq!(merged.to_string_reject(), original);
+    assert_eq!(conflicts, vec![MergeConflict {
+        start: 4,
+        end: 19,
+        line: 1,
+        original: "two three four.".to_string(),
+        authors: vec!["@a".to_string(), "@b".to_string()]
+    }]);
+
+    // insertions at the same place by different authors compete
+    let ours = make_node_from_string("The ++[black  @a ]++cat sat.".to_string()).unwrap();
+    let theirs = make_node_from_string("The ++[fat  @b ]++cat sat.".to_string()).unwrap();
+    let (merged, conflicts) = merge_suggestions("The cat sat.", ours, theirs).unwrap();
+    assert_eq!(merged.to_string_suggestion(), "The ++[black  @a ]++++[fat  @b ]++cat sat.");
+    assert_eq!(conflicts, vec![MergeConflict {
+        start: 4,
+        end: 4,
+        line: 1,
+        original: String::new(),
+        authors: vec!["@a".to_string(), "@b".to_string()]
+    }]);
+
+    // unsigned insertions at the same place compete too
+    let ours = make_node_from_string("The ++[black ]++cat sat.".to_string()).unwrap();
+    let theirs = make_node_from_string("The ++[fat ]++cat sat.".to_string()).unwrap();
+    let (merged, conflicts) = merge_suggestions("The cat sat.", ours, theirs).unwrap();
+    assert_eq!(merged.to_string_suggestion(), "The ++[black ]++++[fat ]++cat sat.");
+    assert_eq!(conflicts.len(), 1);
+    assert!(conflicts[0].authors.is_empty());
+
+    // a shared, unsigned deletion doesn't hide who made each addition
+    let ours = make_node_from_string("The --[cat]--++[dog @a]++ sat.".to_string()).unwrap();
+    let theirs = make_node_from_string("The --[cat]--++[cow @b]++ sat.".to_string()).unwrap();
+    let (merged, conflicts) = merge_suggestions("The cat sat.", ours, theirs).unwrap();
+    assert_eq!(merged.to_string_suggestion(), "The --[cat]--++[dog @a ]++++[cow @b ]++ sat.");
+    assert_eq!(conflicts.len(), 1);
+
+    // a side that only deletes gets no empty addition
+    let ours = make_node_from_string("The --[cat @a]-- sat.".to_string()).unwrap();
+    let theirs = make_node_from_string("The --[cat]--++[cow @b]++ sat.".to_string()).unwrap();
+    let (merged, _) = merge_suggestions("The cat sat.", ours, theirs).unwrap();
+    assert_eq!(merged.to_string_suggestion(), "The --[cat]--++[cow @b ]++ sat.");
+
+    let not_original = make_node_from_string("Something else.".to_string()).unwrap();
+    let theirs = make_node_from_string(original.to_string()).unwrap();
+    assert!(merge_suggestions(original, not_original, theirs).is_err());
+}
+
+
+#[test]
+fn test_merge_three_way() {
+    use crate::make_node_from_string;
+
+    let base = make_node_from_string(
+        "One ++[two @a ]++three.\nFour --[five @a]-- six.".to_string()
+    ).unwrap();
+    // ours accepts the addition and adds a suggestion; theirs rejects 
+    // the deletion and adds another
+    let ours = make_node_from_string(
+        "One two three++[ and a half @b]++.\nFour --[five @a]-- six.".to_string()
+    ).unwrap();
+    let theirs = make_node_from_string(
+        "One ++[two @a ]++three.\nFour five six%%[Done? @c]%%.".to_string()
+    ).unwrap();
+    let (merged, conflicts) = merge_three_way(base, ours, theirs).unwrap();
+    assert_eq!(merged.to_string_suggestion(), 
+        "One two three++[ and a half @b]++.\nFour five six%%[Done? @c]%%.");
+    assert!(conflicts.is_empty());
+
+    // both sides edit the same text
+    let base = make_node_from_string("One two.".to_string()).unwrap();
+    let ours = make_node_from_string("One 2.".to_string()).unwrap();
+    let theirs = make_node_from_string("One II.".to_string()).unwrap();
+    let (merged, conflicts) = merge_three_way(base, ours, theirs).unwrap();
+    assert_eq!(merged.to_string_suggestion(), "One --[two.]--++[2.]++++[II.]++");
+    assert_eq!(conflicts.len(), 1);
+
+    // both sides edit different text
+    let base = make_node_from_string("Para one text.\nPara two text.".to_string()).unwrap();
+    let ours = make_node_from_string(
+        "Para ONE text.\nPara two text.%%[Louder? @a]%%".to_string()
+    ).unwrap();
+    let theirs = make_node_from_string("Para one text.\nPara TWO text.".to_string()).unwrap();
+    let (merged, conflicts) = merge_three_way(base, ours, theirs).unwrap();
+    assert_eq!(merged.to_string_suggestion(), "Para ONE text.\nPara TWO text.%%[Louder? @a]%%");
+    assert!(conflicts.is_empty());
+}
+
+
+#[test]
+fn test_rebase() {
+    use crate::make_node_from_string;
+
+    let suggestions = make_node_from_string(
+        "One ++[big @a ]++two three.%%[Hmm. @b]%% Four --[five @a]-- six.".to_string()
+    ).unwrap();
+    let new_base = "Zero. One two. Four fifty six.";
+    let (rebased, conflicts) = rebase_suggestions(suggestions, new_base);
+
+    assert_eq!(rebased.to_string_suggestion(), 
+        "Zero. One ++[big @a ]++two.%%[Hmm. @b]%% Four %%[Could not apply deletion of \"five\" @a]%%fifty six.");
+    assert_eq!(rebased.to_string_reject(), new_base);
+    assert_eq!(conflicts.len(), 1);
+    assert_eq!(conflicts[0].original, "five");
+
+    // a replacement whose deleted text changed is dropped as a whole
+    let suggestions = make_node_from_string("The --[cat @a]--++[dog @a]++ sat".to_string()).unwrap();
+    let (rebased, conflicts) = rebase_suggestions(suggestions, "The kitten sat");
+    assert_eq!(rebased.to_string_suggestion(), 
+        "The %%[Could not apply replacement of \"cat\" with \"dog\" @a]%%kitten sat");
+    assert_eq!(rebased.to_string_accept(), "The kitten sat");
+    assert_eq!(conflicts.len(), 1);
+}
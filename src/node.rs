@@ -17,7 +17,7 @@ pub struct Node {
     pub kind: NodeKind
 }
 
-#[derive(PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum NodeKind {
     Root,
     Addition,
@@ -83,11 +83,6 @@ impl Node {
     }
 
 
-    pub fn to_string_html(&self) -> Result<String> {
-        self.to_string_visit(Self::html_visitor)
-    }
-    
-
     fn tex_visitor(n: &Node) -> Result<(String, String)> {
         let author_closer =  n.author_clean().unwrap_or(String::from(""));
         let author_closer = author_closer + "}";
@@ -103,21 +98,6 @@ impl Node {
     }
 
 
-    fn html_visitor(n: &Node) -> Result<(String, String)> {
-        let author_closer =  n.author_clean().unwrap_or(String::from(""));
-        let author_closer = format!(" {}</aside>", author_closer);
-        let author_closer = author_closer.as_str();
-        let r = match n.kind {
-            // requires color and ulem packages
-            NodeKind::Addition => ("<ins>", "</ins>"),
-            NodeKind::Deletion => ("<del>", "</del>"),
-            NodeKind::Comment => ("<aside>", author_closer),
-            NodeKind::Root => ("", "")
-        };
-        Ok((r.0.to_string(), r.1.to_string()))
-    }
-
-
     /// Return a string representing the Node with all changes accepted.
     pub fn to_string_accept (&self) -> String {
         // if type is Addition or Root, send contents
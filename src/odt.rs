@@ -0,0 +1,409 @@
+use crate::node::{Node, NodeKind};
+
+use crate::xml::{Piece, Change, Builder, UNKNOWN_AUTHOR, flatten, escape, read_zip_file, attribute, handle_from};
+
+use anyhow::{Result, Context};
+use quick_xml::events::Event;
+use quick_xml::name::QName;
+use quick_xml::reader::Reader;
+use regex::Regex;
+use zip::{ZipArchive, CompressionMethod};
+use zip::write::{ZipWriter, SimpleFileOptions};
+
+use std::collections::HashMap;
+use std::io::{Read, Seek, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+
+const MIMETYPE: &str = "application/vnd.oasis.opendocument.text";
+
+const MANIFEST: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<manifest:manifest xmlns:manifest="urn:oasis:names:tc:opendocument:xmlns:manifest:1.0" manifest:version="1.2">
+<manifest:file-entry manifest:full-path="/" manifest:version="1.2" manifest:media-type="application/vnd.oasis.opendocument.text"/>
+<manifest:file-entry manifest:full-path="content.xml" manifest:media-type="text/xml"/>
+</manifest:manifest>"#;
+
+const NAMESPACES: &str = r#"xmlns:office="urn:oasis:names:tc:opendocument:xmlns:office:1.0" xmlns:text="urn:oasis:names:tc:opendocument:xmlns:text:1.0" xmlns:dc="http://purl.org/dc/elements/1.1/""#;
+
+
+/// Write `node` as an OpenDocument text file, with additions and
+/// deletions as tracked changes and comments as annotations.
+///
+/// As with [`write_docx`](crate::write_docx), paragraphs are separated
+/// by blank lines, other line breaks become spaces, text inside several
+/// suggestions is marked with the innermost one, and unsigned
+/// suggestions get the author "Unknown".
+///
+/// # Examples
+///
+/// ```rust
+/// # use suggestions::{make_node_from_string, write_odt};
+/// let node = make_node_from_string("Hello ++[world @alice]++!".to_string()).unwrap();
+/// let mut odt = std::io::Cursor::new(Vec::new());
+/// write_odt(&node, &mut odt).unwrap();
+/// ```
+pub fn write_odt<W: Write + Seek>(node: &Node, writer: W) -> Result<()> {
+    let mut pieces = Vec::new();
+    flatten(node, None, &mut pieces);
+    let re_break = Regex::new(r"\s*\n[ \t\r]*\n\s*").unwrap();
+    let date = timestamp();
+
+    let mut body = String::from("<text:p>");
+    let mut regions = String::new();
+    let mut ids = 1..;
+    // true at the start of a paragraph or after a space
+    let mut space = true;
+    for piece in pieces {
+        match piece {
+            Piece::Text(text, Some(change)) if change.kind == NodeKind::Deletion => {
+                let id = ids.next().unwrap();
+                let paragraphs = re_break.split(&text)
+                    .map(|p| format!("<text:p>{}</text:p>", encode(p, &mut true)))
+                    .collect::<String>();
+                regions.push_str(&format!("<text:changed-region text:id=\"ct{}\"><text:deletion>{}{}</text:deletion></text:changed-region>",
+                    id, change_info(&change, &date), paragraphs));
+                body.push_str(&format!("<text:change text:change-id=\"ct{}\"/>", id));
+            },
+            Piece::Text(text, change) => {
+                let id = change.as_ref().map(|change| {
+                    let id = ids.next().unwrap();
+                    regions.push_str(&format!("<text:changed-region text:id=\"ct{}\"><text:insertion>{}</text:insertion></text:changed-region>",
+                        id, change_info(change, &date)));
+                    body.push_str(&format!("<text:change-start text:change-id=\"ct{}\"/>", id));
+                    id
+                });
+                for (i, line) in re_break.split(&text).enumerate() {
+                    // a break at the very start of the document isn't a paragraph
+                    if i > 0 && body != "<text:p>" {
+                        body.push_str("</text:p><text:p>");
+                        space = true;
+                    }
+                    let line = if body.ends_with("<text:p>") { line.trim_start() } else { line };
+                    body.push_str(&encode(line, &mut space));
+                }
+                if let Some(id) = id {
+                    body.push_str(&format!("<text:change-end text:change-id=\"ct{}\"/>", id));
+                }
+            },
+            Piece::Comment(text, author) => {
+                body.push_str(&format!("<office:annotation><dc:creator>{}</dc:creator><dc:date>{}</dc:date>",
+                    escape(author.as_deref().unwrap_or(UNKNOWN_AUTHOR)), date));
+                for line in text.lines() {
+                    body.push_str(&format!("<text:p>{}</text:p>", encode(line, &mut true)));
+                }
+                body.push_str("</office:annotation>");
+            }
+        }
+    }
+    body.push_str("</text:p>");
+
+    let content = format!("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+        <office:document-content {} office:version=\"1.2\"><office:body><office:text>\
+        <text:tracked-changes>{}</text:tracked-changes>{}</office:text></office:body></office:document-content>",
+        NAMESPACES, regions, body);
+
+    let mut zip = ZipWriter::new(writer);
+    // the mimetype must come first, uncompressed
+    zip.start_file("mimetype", SimpleFileOptions::default().compression_method(CompressionMethod::Stored))?;
+    zip.write_all(MIMETYPE.as_bytes())?;
+    for (name, contents) in [("META-INF/manifest.xml", MANIFEST), ("content.xml", &content)] {
+        zip.start_file(name, SimpleFileOptions::default())?;
+        zip.write_all(contents.as_bytes())?;
+    }
+    zip.finish()?;
+    Ok(())
+}
+
+
+fn change_info(change: &Change, date: &str) -> String {
+    let author = change.author.as_deref().unwrap_or(UNKNOWN_AUTHOR);
+    format!("<office:change-info><dc:creator>{}</dc:creator><dc:date>{}</dc:date></office:change-info>",
+        escape(author), date)
+}
+
+
+/// Escape `text` for a paragraph. OpenDocument collapses runs of
+/// spaces, so all but the first space are written as `<text:s/>`.
+/// `space` says whether the text follows a space, and is updated.
+fn encode(text: &str, space: &mut bool) -> String {
+    let mut output = String::new();
+    let mut spaces = 0;
+    for c in text.chars() {
+        if matches!(c, ' ' | '\n' | '\r') {
+            if *space {
+                spaces += 1;
+            } else {
+                output.push(' ');
+                *space = true;
+            }
+            continue;
+        }
+        push_spaces(&mut output, spaces);
+        spaces = 0;
+        *space = false;
+        match c {
+            '\t' => output.push_str("<text:tab/>"),
+            '&' => output.push_str("&amp;"),
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            c => output.push(c)
+        }
+    }
+    push_spaces(&mut output, spaces);
+    output
+}
+
+
+fn push_spaces(output: &mut String, count: usize) {
+    match count {
+        0 => {},
+        1 => output.push_str("<text:s/>"),
+        n => output.push_str(&format!("<text:s text:c=\"{}\"/>", n))
+    }
+}
+
+
+/// The current time in UTC, like `2024-01-31T12:00:00`.
+fn timestamp() -> String {
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let (days, secs) = ((secs / 86400) as i64, secs % 86400);
+    // Howard Hinnant's civil_from_days
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}", year, month, day, secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+
+/// Read an OpenDocument text file, with tracked insertions and
+/// deletions as suggestions and annotations as comments.
+///
+/// Authors' names become handles with spaces removed, as in
+/// [`read_docx`](crate::read_docx). Paragraphs are separated by blank
+/// lines.
+///
+/// # Errors
+///
+/// Returns an error if the file is not a valid OpenDocument file.
+pub fn read_odt<R: Read + Seek>(reader: R) -> Result<Node> {
+    let mut zip = ZipArchive::new(reader).context("Not an OpenDocument file")?;
+    let content = read_zip_file(&mut zip, "content.xml")?
+        .context("Not an OpenDocument file: no content.xml")?;
+    let regions = read_regions(&content)?;
+
+    let mut builder = Builder::new();
+    let mut insertions: Vec<(String, Change)> = Vec::new();
+    let mut pending_break: Option<Option<Change>> = None;
+    let mut in_paragraph = 0;
+    let mut xml = Reader::from_str(&content);
+
+    loop {
+        let event = xml.read_event()?;
+        let is_start = matches!(event, Event::Start(_));
+        match event {
+            Event::Start(e) | Event::Empty(e) => match e.name().as_ref() {
+                b"text:tracked-changes" if is_start => {
+                    xml.read_to_end(QName(b"text:tracked-changes"))?;
+                },
+                b"office:annotation" if is_start => {
+                    let (author, paragraphs) = read_block(&mut xml, b"office:annotation")?;
+                    builder.push_break(&mut pending_break);
+                    builder.push_comment(&paragraphs.join("\n"), handle_from(author).as_deref());
+                },
+                b"text:change-start" => {
+                    let id = attribute(&e, "text:change-id")?.unwrap_or_default();
+                    if let Some((change, _)) = regions.get(&id) {
+                        insertions.push((id, change.clone()));
+                    }
+                },
+                b"text:change-end" => {
+                    let id = attribute(&e, "text:change-id")?.unwrap_or_default();
+                    insertions.retain(|(i, _)| *i != id);
+                },
+                b"text:change" => {
+                    let id = attribute(&e, "text:change-id")?.unwrap_or_default();
+                    if let Some((change, text)) = regions.get(&id) {
+                        builder.push_break(&mut pending_break);
+                        builder.push_text(text, Some(change));
+                    }
+                },
+                b"text:p" | b"text:h" if is_start => in_paragraph += 1,
+                b"text:p" | b"text:h" => pending_break = Some(insertions.last().map(|(_, c)| c.clone())),
+                name => if let Some(text) = inline_text(name, &e)? {
+                    builder.push_break(&mut pending_break);
+                    builder.push_text(&text, insertions.last().map(|(_, c)| c));
+                }
+            },
+            Event::End(e) => if matches!(e.name().as_ref(), b"text:p" | b"text:h") {
+                in_paragraph -= 1;
+                pending_break = Some(insertions.last().map(|(_, c)| c.clone()));
+            },
+            Event::Text(t) if in_paragraph > 0 => {
+                builder.push_break(&mut pending_break);
+                builder.push_text(&collapse(&t.unescape()?), insertions.last().map(|(_, c)| c));
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(builder.root)
+}
+
+
+/// Read the `text:tracked-changes` regions into a map from change ids to
+/// the change and, for deletions, the deleted text.
+fn read_regions(xml: &str) -> Result<HashMap<String, (Change, String)>> {
+    let mut regions = HashMap::new();
+    let mut id = String::new();
+    let mut reader = Reader::from_str(xml);
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) if e.name().as_ref() == b"text:changed-region" => {
+                id = attribute(&e, "text:id")?.or(attribute(&e, "xml:id")?).unwrap_or_default();
+            },
+            Event::Start(e) if matches!(e.name().as_ref(), b"text:insertion" | b"text:deletion") => {
+                let kind = if e.name().as_ref() == b"text:insertion" { NodeKind::Addition } else { NodeKind::Deletion };
+                let end = e.name().as_ref().to_vec();
+                let (author, paragraphs) = read_block(&mut reader, &end)?;
+                let change = Change { kind, author: handle_from(author) };
+                regions.insert(id.clone(), (change, paragraphs.join("\n\n")));
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(regions)
+}
+
+
+/// Read up to the end tag `end`, returning the `dc:creator` and the
+/// text of each paragraph.
+fn read_block(reader: &mut Reader<&[u8]>, end: &[u8]) -> Result<(Option<String>, Vec<String>)> {
+    let mut author = None;
+    let mut paragraphs: Vec<String> = Vec::new();
+    let mut in_creator = false;
+    let mut in_paragraph = 0;
+
+    loop {
+        let event = reader.read_event()?;
+        let is_start = matches!(event, Event::Start(_));
+        match event {
+            Event::Start(e) | Event::Empty(e) => match e.name().as_ref() {
+                b"dc:creator" => in_creator = is_start,
+                b"text:p" | b"text:h" => {
+                    paragraphs.push(String::new());
+                    if is_start { in_paragraph += 1; }
+                },
+                name => if let (Some(text), Some(paragraph)) = (inline_text(name, &e)?, paragraphs.last_mut()) {
+                    paragraph.push_str(&text);
+                }
+            },
+            Event::End(e) if e.name().as_ref() == end => break,
+            Event::End(e) => match e.name().as_ref() {
+                b"dc:creator" => in_creator = false,
+                b"text:p" | b"text:h" => in_paragraph -= 1,
+                _ => {}
+            },
+            Event::Text(t) if in_creator => author = Some(t.unescape()?.to_string()),
+            Event::Text(t) if in_paragraph > 0 => if let Some(paragraph) = paragraphs.last_mut() {
+                paragraph.push_str(&collapse(&t.unescape()?));
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok((author, paragraphs))
+}
+
+
+/// The text that the elements `text:s`, `text:tab` and `text:line-break` stand for.
+fn inline_text(name: &[u8], e: &quick_xml::events::BytesStart) -> Result<Option<String>> {
+    Ok(match name {
+        b"text:s" => {
+            let count = attribute(e, "text:c")?.and_then(|c| c.parse().ok()).unwrap_or(1);
+            Some(" ".repeat(count))
+        },
+        b"text:tab" => Some("\t".to_string()),
+        b"text:line-break" => Some("\n".to_string()),
+        _ => None
+    })
+}
+
+
+/// Collapse runs of whitespace to a single space, as OpenDocument does.
+fn collapse(text: &str) -> String {
+    let re_space = Regex::new(r"[ \t\r\n]+").unwrap();
+    re_space.replace_all(text, " ").to_string()
+}
+
+
+#[test]
+fn test_write_odt() {
+    use std::io::Read;
+
+    let node = crate::make_node_from_string(
+        "Tom & Jerry ++[<3 @alice]++.\n\nNew --[paragraph\n\n]--line  %%[Why? @bob]%%.".to_string()
+    ).unwrap();
+    let mut buffer = std::io::Cursor::new(Vec::new());
+    write_odt(&node, &mut buffer).unwrap();
+
+    let mut zip = zip::ZipArchive::new(buffer).unwrap();
+    assert_eq!(zip.by_index(0).unwrap().name(), "mimetype");
+    let mut content = String::new();
+    zip.by_name("content.xml").unwrap().read_to_string(&mut content).unwrap();
+    assert!(content.contains("<text:p>Tom &amp; Jerry <text:change-start text:change-id=\"ct1\"/>&lt;3\
+        <text:change-end text:change-id=\"ct1\"/>.</text:p>"));
+    assert!(content.contains("<text:changed-region text:id=\"ct1\"><text:insertion><office:change-info>\
+        <dc:creator>@alice</dc:creator>"));
+    assert!(content.contains("<dc:creator>Unknown</dc:creator>"));
+    assert!(content.contains("</office:change-info><text:p>paragraph</text:p><text:p></text:p></text:deletion>"));
+    assert!(content.contains("<text:p>New <text:change text:change-id=\"ct2\"/>line <text:s/>\
+        <office:annotation><dc:creator>@bob</dc:creator>"));
+    assert_eq!(content.matches("<text:p>").count(), 5);
+}
+
+
+#[test]
+fn test_read_odt() {
+    let text = "Tom & Jerry ++[<3 @alice]++.\n\nNew --[paragraph\n\n @bob]--%%[Why? @bob]%%  \tthen\n\n\
+        ++[Added paragraph.\n\n @alice]++The end.";
+    let node = crate::make_node_from_string(text.to_string()).unwrap();
+    let mut buffer = std::io::Cursor::new(Vec::new());
+    write_odt(&node, &mut buffer).unwrap();
+
+    let read = read_odt(buffer).unwrap();
+    assert_eq!(read.to_string_suggestion(),
+        "Tom & Jerry ++[<3 @alice ]++.\n\nNew --[paragraph\n\n @bob ]--%%[Why? @bob]%%  \tthen\n\n\
+        ++[Added paragraph.\n\n @alice ]++The end.");
+
+    // a document written by LibreOffice
+    let content = r#"<office:document-content xmlns:office="o" xmlns:text="t" xmlns:dc="d"><office:body><office:text>
+        <text:tracked-changes text:track-changes="false">
+        <text:changed-region xml:id="ct1" text:id="ct1"><text:deletion><office:change-info>
+        <dc:creator>Jane Doe</dc:creator><dc:date>2024-01-01T00:00:00</dc:date></office:change-info>
+        <text:p>a</text:p></text:deletion></text:changed-region>
+        <text:changed-region xml:id="ct2" text:id="ct2"><text:insertion><office:change-info>
+        <dc:creator>Jane Doe</dc:creator><dc:date>2024-01-01T00:00:00</dc:date></office:change-info>
+        </text:insertion></text:changed-region></text:tracked-changes>
+        <text:p text:style-name="P1">It was <office:annotation office:name="__Annotation__1"><dc:creator>Bob</dc:creator>
+        <dc:date>2024-01-01T00:00:00</dc:date><text:p>Nice.</text:p></office:annotation><text:change text:change-id="ct1"/><text:change-start text:change-id="ct2"/>the<text:tab/><text:span text:style-name="T1">best</text:span></text:p>
+        <text:p><text:change-end text:change-id="ct2"/><text:s/>of times.</text:p><office:annotation-end office:name="__Annotation__1"/>
+        </office:text></office:body></office:document-content>"#;
+    let mut buffer = std::io::Cursor::new(Vec::new());
+    let mut zip = ZipWriter::new(&mut buffer);
+    zip.start_file("content.xml", SimpleFileOptions::default()).unwrap();
+    zip.write_all(content.as_bytes()).unwrap();
+    zip.finish().unwrap();
+
+    let read = read_odt(buffer).unwrap();
+    assert_eq!(read.to_string_suggestion(),
+        "It was %%[Nice. @Bob]%%--[a @JaneDoe ]--++[the\tbest\n\n @JaneDoe ]++ of times.");
+}
@@ -0,0 +1,163 @@
+use crate::node::{Node, NodeKind, Chunk, opener, closer};
+
+
+/// Two or more authors' suggestions which touch the same text, found by
+/// [`find_overlaps`]. An editor needs to choose between them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Overlap {
+    pub kind: OverlapKind,
+    /// Byte offset where the overlap starts, in the suggestions text
+    /// given by [`Node::to_string_suggestion()`].
+    pub start: usize,
+    /// Byte offset where the overlap ends.
+    pub end: usize,
+    /// Line number of `start`, counting from 1.
+    pub line: usize,
+    /// The suggestions text from `start` to `end`.
+    pub text: String,
+    /// Authors of the overlapping suggestions. Unsigned suggestions 
+    /// are not listed.
+    pub authors: Vec<String>,
+}
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OverlapKind {
+    /// A deletion contains changes by other authors. Accepting it
+    /// throws those changes away.
+    DeletionWrapsChange,
+    /// Additions by different authors sit side by side, with nothing
+    /// but whitespace or comments between them.
+    CompetingAdditions,
+}
+
+
+/// Find places where different authors' suggestions overlap.
+/// 
+/// # Examples
+/// 
+/// ```rust
+/// # use suggestions::{make_node_from_string, find_overlaps, OverlapKind};
+/// let node = make_node_from_string(
+///     "Let there be --[light ++[and dark @bob]++ @alice]--.".to_string()).unwrap();
+/// let overlaps = find_overlaps(&node);
+/// assert_eq!(overlaps[0].kind, OverlapKind::DeletionWrapsChange);
+/// assert_eq!(overlaps[0].authors, vec!["@alice", "@bob"]);
+/// ```
+pub fn find_overlaps(node: &Node) -> Vec<Overlap> {
+    let text = node.to_string_suggestion();
+    let mut finder = Finder { text: &text, overlaps: Vec::new() };
+    finder.visit(node, 0);
+    finder.overlaps.sort_by_key(|o| o.start);
+    finder.overlaps
+}
+
+
+struct Finder<'a> {
+    text: &'a str,
+    overlaps: Vec<Overlap>
+}
+
+
+impl Finder<'_> {
+    /// Look for overlaps in `node`, which starts at `pos` in the
+    /// suggestions text. Returns where `node` ends.
+    fn visit(&mut self, node: &Node, pos: usize) -> usize {
+        let start = pos;
+        let mut pos = pos + opener(&node.kind).len();
+        // consecutive additions, with their positions
+        let mut run: Vec<(usize, usize, &Node)> = Vec::new();
+
+        for chunk in &node.contents {
+            match chunk {
+                Chunk::TextChunk(text) => {
+                    if ! text.trim().is_empty() {
+                        self.check_run(&mut run);
+                    }
+                    pos += text.len();
+                },
+                Chunk::NodeChunk(nd) => {
+                    let nd_start = pos;
+                    pos = self.visit(nd, pos);
+                    match nd.kind {
+                        NodeKind::Addition => run.push((nd_start, pos, nd)),
+                        NodeKind::Comment => {},
+                        _ => self.check_run(&mut run)
+                    }
+                }
+            }
+        }
+        self.check_run(&mut run);
+        pos += node.author_string.as_ref().map_or(0, |a| a.len());
+        pos += closer(&node.kind).len();
+
+        if node.kind == NodeKind::Deletion {
+            let author = node.author_clean();
+            let mut others = Vec::new();
+            collect_authors(node, &mut others);
+            others.retain(|a| Some(a) != author.as_ref());
+            if ! others.is_empty() {
+                let authors = author.into_iter().chain(others).collect();
+                self.push(OverlapKind::DeletionWrapsChange, start, pos, authors);
+            }
+        }
+
+        pos
+    }
+
+    /// Report `run` if it holds additions by different authors, then clear it.
+    fn check_run(&mut self, run: &mut Vec<(usize, usize, &Node)>) {
+        let mut authors: Vec<Option<String>> = run.iter().map(|(_, _, nd)| nd.author_clean()).collect();
+        authors.sort();
+        authors.dedup();
+        if authors.len() > 1 {
+            let (start, end) = (run[0].0, run[run.len() - 1].1);
+            let authors = authors.into_iter().flatten().collect();
+            self.push(OverlapKind::CompetingAdditions, start, end, authors);
+        }
+        run.clear();
+    }
+
+    fn push(&mut self, kind: OverlapKind, start: usize, end: usize, authors: Vec<String>) {
+        self.overlaps.push(Overlap {
+            kind,
+            start,
+            end,
+            line: self.text[..start].matches('\n').count() + 1,
+            text: self.text[start..end].to_string(),
+            authors
+        });
+    }
+}
+
+
+/// Collect the distinct authors of additions and deletions inside `node`.
+fn collect_authors(node: &Node, authors: &mut Vec<String>) {
+    for chunk in &node.contents {
+        if let Chunk::NodeChunk(nd) = chunk {
+            if let (NodeKind::Addition | NodeKind::Deletion, Some(author)) = (&nd.kind, nd.author_clean()) {
+                if ! authors.contains(&author) {
+                    authors.push(author);
+                }
+            }
+            collect_authors(nd, authors);
+        }
+    }
+}
+
+
+#[test]
+fn test_find_overlaps() {
+    let text = "And God said,\n%%[first try! @wycliffe]%%\n--[Light be made. @tyndale]--\n\
+        ++[Let there be lyghte. @tyndale]++\n++[Let there be light. @kjv]++\n\
+        Then ++[one ++[two @b]++ @a]++ --[three @a]--++[four @b]++.";
+    let node = crate::make_node_from_string(text.to_string()).unwrap();
+    let overlaps = find_overlaps(&node);
+
+    assert_eq!(overlaps.len(), 1);
+    assert_eq!(overlaps[0].kind, OverlapKind::CompetingAdditions);
+    assert_eq!(overlaps[0].line, 4);
+    assert_eq!(overlaps[0].authors, vec!["@kjv", "@tyndale"]);
+    assert_eq!(overlaps[0].text, 
+        "++[Let there be lyghte. @tyndale]++\n++[Let there be light. @kjv]++");
+}
@@ -0,0 +1,343 @@
+use crate::node::{Node, NodeKind, Chunk};
+use crate::diff::{DiffOptions, diff_strings};
+
+use anyhow::{Result, bail};
+use regex::Regex;
+use similar::TextDiff;
+
+
+/// The changes to one file in a unified diff, as read by [`parse_patch`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Patch {
+    /// Path of the original file, without git's `a/` prefix. `None` if
+    /// the file is new.
+    pub old_path: Option<String>,
+    /// Path of the changed file, without git's `b/` prefix. `None` if
+    /// the file was deleted.
+    pub new_path: Option<String>,
+    /// Author from a `From:` or `Author:` header, like
+    /// `Jane Doe <jane@example.com>`.
+    pub author: Option<String>,
+    pub hunks: Vec<Hunk>,
+}
+
+
+/// A single `@@ ... @@` hunk of a [`Patch`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Hunk {
+    /// Line in the original file where the hunk starts, counting from 1.
+    pub old_start: usize,
+    /// Lines of the hunk. The first character is ' ', '-' or '+'. Each
+    /// line keeps its newline, unless the patch says there is none.
+    pub lines: Vec<String>,
+}
+
+
+impl Hunk {
+    /// The hunk's text in the original file.
+    pub fn old_text(&self) -> String {
+        self.text_without('+')
+    }
+
+    /// The hunk's text in the changed file.
+    pub fn new_text(&self) -> String {
+        self.text_without('-')
+    }
+
+    fn text_without(&self, skip: char) -> String {
+        self.lines.iter()
+            .filter(|l| ! l.starts_with(skip))
+            .map(|l| &l[1..])
+            .collect()
+    }
+}
+
+
+/// Read a unified diff, as written by `diff -u`, `git diff` or
+/// `git format-patch`.
+///
+/// Text outside file headers and hunks, such as commit messages, is
+/// skipped.
+///
+/// # Errors
+///
+/// Returns an error if a hunk is malformed or has the wrong number of lines.
+pub fn parse_patch(text: &str) -> Result<Vec<Patch>> {
+    let re_hunk = Regex::new(r"^@@ -(\d+)(?:,(\d+))? \+\d+(?:,(\d+))? @@").unwrap();
+    let mut patches: Vec<Patch> = Vec::new();
+    let mut author = None;
+    let mut lines = text.split_inclusive('\n').peekable();
+
+    while let Some(line) = lines.next() {
+        if let Some(a) = line.strip_prefix("From: ").or(line.strip_prefix("Author: ")) {
+            author = Some(a.trim().to_string());
+        } else if line.starts_with("--- ") && lines.peek().is_some_and(|l| l.starts_with("+++ ")) {
+            let new_line = lines.next().unwrap();
+            patches.push(Patch {
+                old_path: header_path(&line[4..], "a/"),
+                new_path: header_path(&new_line[4..], "b/"),
+                author: author.clone(),
+                hunks: Vec::new()
+            });
+        } else if let Some(caps) = re_hunk.captures(line) {
+            let Some(patch) = patches.last_mut() else {
+                bail!("Found a hunk before any file header: {:?}", line.trim_end());
+            };
+            let count = |i: usize| caps.get(i).map_or(1, |m| m.as_str().parse().unwrap());
+            let (mut old_left, mut new_left) = (count(2), count(3));
+            let mut hunk = Hunk { old_start: caps[1].parse()?, lines: Vec::new() };
+
+            while old_left + new_left > 0 {
+                let Some(line) = lines.next() else {
+                    bail!("Patch ended in the middle of a hunk at line {}", hunk.old_start);
+                };
+                // some tools strip the space from empty context lines
+                let line = if line == "\n" || line == "\r\n" { format!(" {}", line) } else { line.to_string() };
+                match line.chars().next() {
+                    Some(' ') if old_left > 0 && new_left > 0 => { old_left -= 1; new_left -= 1; },
+                    Some('-') if old_left > 0 => old_left -= 1,
+                    Some('+') if new_left > 0 => new_left -= 1,
+                    Some('\\') => { strip_newline(&mut hunk); continue; },
+                    _ => bail!("Unexpected line in hunk at line {}: {:?}", hunk.old_start, line.trim_end())
+                }
+                hunk.lines.push(line);
+            }
+            if lines.peek().is_some_and(|l| l.starts_with('\\')) {
+                lines.next();
+                strip_newline(&mut hunk);
+            }
+            patch.hunks.push(hunk);
+        }
+    }
+
+    Ok(patches)
+}
+
+
+/// Get the path from a `---` or `+++` header line.
+fn header_path(header: &str, prefix: &str) -> Option<String> {
+    // diff -u puts a tab and a timestamp after the path
+    let path = header.split('\t').next().unwrap().trim_end();
+    if path == "/dev/null" {
+        return None;
+    }
+    Some(path.strip_prefix(prefix).unwrap_or(path).to_string())
+}
+
+
+/// Handle a "\ No newline at end of file" line.
+fn strip_newline(hunk: &mut Hunk) {
+    if let Some(last) = hunk.lines.last_mut() {
+        if last.ends_with('\n') {
+            last.pop();
+            if last.ends_with('\r') {
+                last.pop();
+            }
+        }
+    }
+}
+
+
+/// Apply `patch` to `source`, showing the changes as suggestions.
+///
+/// Each hunk is compared using `opts`, so line-level changes in the
+/// patch become word-level suggestions by default. If a hunk is not
+/// found at the line the patch gives, nearby lines are searched.
+///
+/// # Examples
+///
+/// ```rust
+/// # use suggestions::{parse_patch, patch_to_suggestions, DiffOptions};
+/// let patch = "--- a/ark.txt\n+++ b/ark.txt\n@@ -1 +1 @@\n\
+///     -The animals went in two by two.\n+The animals went in four by four.\n";
+/// let patches = parse_patch(patch).unwrap();
+/// let node = patch_to_suggestions("The animals went in two by two.\n",
+///     &patches[0], &DiffOptions::default()).unwrap();
+/// assert_eq!(node.to_string_suggestion(),
+///     "The animals went in --[two]--++[four]++ by --[two.]--++[four.]++\n");
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if a hunk's original text can't be found in `source`.
+pub fn patch_to_suggestions(source: &str, patch: &Patch, opts: &DiffOptions) -> Result<Node> {
+    let lines: Vec<&str> = source.split_inclusive('\n').collect();
+    let mut root = Node::root();
+    let mut pos = 0;
+
+    for (i, hunk) in patch.hunks.iter().enumerate() {
+        let old_lines: Vec<&str> = hunk.lines.iter()
+            .filter(|l| ! l.starts_with('+'))
+            .map(|l| &l[1..])
+            .collect();
+        // an empty "-0,0" range starts before line 1; otherwise after
+        // the line before old_start
+        let expected = if old_lines.is_empty() { hunk.old_start } else { hunk.old_start.saturating_sub(1) };
+        let Some(start) = find_lines(&lines, &old_lines, pos, expected) else {
+            bail!("Hunk {} does not apply: could not find the original text near line {}",
+                i + 1, hunk.old_start);
+        };
+
+        let before: String = lines[pos..start].concat();
+        if ! before.is_empty() {
+            root.contents.push(Chunk::TextChunk(before));
+        }
+        let node = diff_strings(&hunk.old_text(), &hunk.new_text(), opts);
+        root.contents.extend(node.contents);
+        pos = start + old_lines.len();
+    }
+
+    let rest: String = lines[pos..].concat();
+    if ! rest.is_empty() {
+        root.contents.push(Chunk::TextChunk(rest));
+    }
+    Ok(root)
+}
+
+
+/// Find `target` in `lines`, starting at or after `from`, as close as
+/// possible to `expected`.
+fn find_lines(lines: &[&str], target: &[&str], from: usize, expected: usize) -> Option<usize> {
+    if lines.len() < target.len() {
+        return None;
+    }
+    let last = lines.len() - target.len();
+    let matches_at = |i: usize| (from..=last).contains(&i) && lines[i..i + target.len()] == *target;
+    (0..=lines.len()).find_map(|offset| {
+        [expected.checked_add(offset), expected.checked_sub(offset)]
+            .into_iter()
+            .flatten()
+            .find(|&i| matches_at(i))
+    })
+}
+
+
+/// Write `node` as a unified diff from its rejected to its accepted text.
+///
+/// `path` is used in the `--- a/` and `+++ b/` headers, so the result can be
+/// applied with `patch -p1` or `git apply`. `context` is the number of
+/// unchanged lines shown around each change. If `comments` is true,
+/// comments are listed before the headers as `#` lines with their line 
+/// number in the original text; `patch` and `git apply` ignore these.
+///
+/// # Examples
+///
+/// ```rust
+/// # use suggestions::{make_node_from_string, suggestions_to_patch};
+/// let node = make_node_from_string("Hello --[world]--++[there]++!\n".to_string()).unwrap();
+/// assert_eq!(suggestions_to_patch(&node, "hello.txt", 3, false),
+///     "--- a/hello.txt\n+++ b/hello.txt\n@@ -1 +1 @@\n-Hello world!\n+Hello there!\n");
+/// ```
+pub fn suggestions_to_patch(node: &Node, path: &str, context: usize, comments: bool) -> String {
+    let mut output = String::new();
+    if comments {
+        let mut found = Vec::new();
+        collect_comments(node, false, &mut 1, &mut found);
+        for (line, comment) in found {
+            output.push_str(&format!("# Comment at line {}: {}\n", line, comment));
+        }
+    }
+
+    let old = node.to_string_reject();
+    let new = node.to_string_accept();
+    let diff = TextDiff::from_lines(&old, &new);
+    if diff.ratio() < 1.0 {
+        output.push_str(&diff.unified_diff()
+            .context_radius(context)
+            .header(&format!("a/{}", path), &format!("b/{}", path))
+            .to_string());
+    }
+    output
+}
+
+
+/// Collect the comments in `node`, with the line where each one falls
+/// in the rejected text.
+fn collect_comments(node: &Node, in_addition: bool, line: &mut usize, found: &mut Vec<(usize, String)>) {
+    for chunk in &node.contents {
+        match chunk {
+            Chunk::TextChunk(text) => if ! in_addition {
+                *line += text.matches('\n').count();
+            },
+            Chunk::NodeChunk(nd) => match nd.kind {
+                NodeKind::Comment => {
+                    let mut text: Vec<String> = nd.contents.iter().filter_map(|c| match c {
+                        Chunk::TextChunk(t) => Some(t.split_whitespace().collect::<Vec<_>>().join(" ")),
+                        Chunk::NodeChunk(_) => None
+                    }).collect();
+                    text.extend(nd.author_clean());
+                    found.push((*line, text.join(" ").trim().to_string()));
+                },
+                NodeKind::Addition => collect_comments(nd, true, line, found),
+                _ => collect_comments(nd, in_addition, line, found)
+            }
+        }
+    }
+}
+
+
+/// Turn a patch author like `Jane Doe <jane.doe@example.com>` into a
+/// handle like `@jane.doe`. Without an email, the name is used with
+/// spaces removed. A name that is already a handle is kept as it is.
+pub fn author_handle(author: &str) -> String {
+    let re = Regex::new(r"<([^@>\s]+)@[^>]*>").unwrap();
+    let name = match re.captures(author) {
+        Some(caps) => caps[1].to_string(),
+        None => author.split_whitespace().collect::<String>().trim_start_matches('@').to_string()
+    };
+    format!("@{}", name)
+}
+
+
+#[test]
+fn test_patch() {
+    let source = "one\ntwo\nthree\nfour\nfive\nsix\nseven\n";
+    let patch = "From: Jane Doe <jane@example.com>\nSubject: numbers\n\n\
+        --- a/numbers.txt\t2024-01-01\n+++ b/numbers.txt\t2024-01-02\n\
+        @@ -1,3 +1,3 @@\n one\n-two\n+2\n three\n\
+        @@ -5,2 +5,3 @@\n five\n+five and a half\n six\n";
+    let patches = parse_patch(patch).unwrap();
+
+    assert_eq!(patches.len(), 1);
+    assert_eq!(patches[0].old_path.as_deref(), Some("numbers.txt"));
+    assert_eq!(patches[0].hunks.len(), 2);
+    assert_eq!(author_handle(patches[0].author.as_ref().unwrap()), "@jane");
+    assert_eq!(author_handle("Jane Doe"), "@JaneDoe");
+    assert_eq!(author_handle("@jane"), "@jane");
+
+    let opts = DiffOptions { author: Some("@jane".to_string()), ..Default::default() };
+    let node = patch_to_suggestions(source, &patches[0], &opts).unwrap();
+    assert_eq!(node.to_string_reject(), source);
+    assert_eq!(node.to_string_accept(), "one\n2\nthree\nfour\nfive\nfive and a half\nsix\nseven\n");
+    assert!(node.to_string_suggestion().contains("--[two @jane ]--++[2 @jane ]++"));
+
+    // hunks are found even when lines have moved
+    let moved = format!("zero\n{}", source);
+    let node = patch_to_suggestions(&moved, &patches[0], &opts).unwrap();
+    assert_eq!(node.to_string_reject(), moved);
+
+    let no_newline = "--- a/x\n+++ b/x\n@@ -1 +1 @@\n-old\n\\ No newline at end of file\n+new\n";
+    let patches = parse_patch(no_newline).unwrap();
+    let node = patch_to_suggestions("old", &patches[0], &DiffOptions::default()).unwrap();
+    assert_eq!(node.to_string_accept(), "new\n");
+
+    assert!(patch_to_suggestions("nothing like it\n", &patches[0], &DiffOptions::default()).is_err());
+}
+
+
+#[test]
+fn test_suggestions_to_patch() {
+    let text = "one\ntwo %%[Really? @bob]%%\n++[two and a half\n]++three\nfour\nfive\nsix\n\
+        seven\neight\nnine --[ten]--++[10 @alice]++\n";
+    let node = crate::make_node_from_string(text.to_string()).unwrap();
+    let patch = suggestions_to_patch(&node, "numbers.txt", 1, true);
+
+    assert!(patch.starts_with("# Comment at line 2: Really? @bob\n--- a/numbers.txt\n"));
+    assert!(patch.contains("@@ -2,2 +2,3 @@\n two \n+two and a half\n three\n"));
+    assert!(patch.contains("-nine ten\n+nine 10\n"));
+
+    // the patch round-trips through from-patch
+    let patches = parse_patch(&patch).unwrap();
+    let rebuilt = patch_to_suggestions(&node.to_string_reject(), &patches[0], &DiffOptions::default()).unwrap();
+    assert_eq!(rebuilt.to_string_accept(), node.to_string_accept());
+}
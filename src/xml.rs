@@ -0,0 +1,145 @@
+use crate::node::{Node, NodeKind, Chunk};
+
+use crate::patch::author_handle;
+
+use anyhow::Result;
+use quick_xml::events::BytesStart;
+use zip::ZipArchive;
+
+use std::io::{Read, Seek};
+
+
+/// Word and ODT documents need an author for every change, so unsigned
+/// suggestions get this.
+pub(crate) const UNKNOWN_AUTHOR: &str = "Unknown";
+
+
+/// A piece of text, or a comment, from a flattened [`Node`] tree.
+pub(crate) enum Piece {
+    /// Text, and the innermost change it belongs to, if any.
+    Text(String, Option<Change>),
+    Comment(String, Option<String>)
+}
+
+
+#[derive(Clone)]
+pub(crate) struct Change {
+    pub(crate) kind: NodeKind,
+    pub(crate) author: Option<String>
+}
+
+
+/// Flatten `node` into pieces of text, each marked with the innermost
+/// change it is in. Text inside a deletion stays deleted.
+pub(crate) fn flatten(node: &Node, change: Option<Change>, pieces: &mut Vec<Piece>) {
+    for chunk in &node.contents {
+        match chunk {
+            Chunk::TextChunk(text) => pieces.push(Piece::Text(text.clone(), change.clone())),
+            Chunk::NodeChunk(nd) => match nd.kind {
+                NodeKind::Comment => {
+                    let text = nd.contents.iter().filter_map(|c| match c {
+                        Chunk::TextChunk(t) => Some(t.as_str()),
+                        Chunk::NodeChunk(_) => None
+                    }).collect::<String>();
+                    pieces.push(Piece::Comment(text.trim().to_string(), nd.author_clean()));
+                },
+                NodeKind::Addition if change.as_ref().is_some_and(|c| c.kind == NodeKind::Deletion) => {
+                    flatten(nd, change.clone(), pieces);
+                },
+                _ => {
+                    let inner = Change { kind: nd.kind, author: nd.author_clean() };
+                    flatten(nd, Some(inner), pieces);
+                }
+            }
+        }
+    }
+}
+
+
+/// Builds a Node, merging neighbouring text with the same change.
+pub(crate) struct Builder {
+    pub(crate) root: Node
+}
+
+
+impl Builder {
+    pub(crate) fn new() -> Builder {
+        Builder { root: Node::root() }
+    }
+
+    pub(crate) fn push_text(&mut self, text: &str, change: Option<&Change>) {
+        let Some(change) = change else {
+            if let Some(Chunk::TextChunk(last)) = self.root.contents.last_mut() {
+                last.push_str(text);
+            } else {
+                self.root.contents.push(Chunk::TextChunk(text.to_string()));
+            }
+            return;
+        };
+        let author_string = change.author.as_ref().map(|a| format!(" {} ", a));
+        if let Some(Chunk::NodeChunk(last)) = self.root.contents.last_mut() {
+            if last.kind == change.kind && last.author_string == author_string {
+                if let Some(Chunk::TextChunk(last_text)) = last.contents.last_mut() {
+                    last_text.push_str(text);
+                    return;
+                }
+            }
+        }
+        self.root.contents.push(Chunk::NodeChunk(Node {
+            author_string,
+            contents: vec![Chunk::TextChunk(text.to_string())],
+            kind: change.kind
+        }));
+    }
+
+    pub(crate) fn push_comment(&mut self, text: &str, author: Option<&str>) {
+        self.root.contents.push(Chunk::NodeChunk(Node {
+            author_string: author.map(|a| format!(" {}", a)),
+            contents: vec![Chunk::TextChunk(text.to_string())],
+            kind: NodeKind::Comment
+        }));
+    }
+
+    /// Write a paragraph break, if one is waiting, before new content.
+    pub(crate) fn push_break(&mut self, pending: &mut Option<Option<Change>>) {
+        if let Some(change) = pending.take() {
+            if ! self.root.contents.is_empty() {
+                self.push_text("\n\n", change.as_ref());
+            }
+        }
+    }
+}
+
+
+pub(crate) fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+
+pub(crate) fn attribute(e: &BytesStart, name: &str) -> Result<Option<String>> {
+    Ok(match e.try_get_attribute(name)? {
+        Some(a) => Some(a.unescape_value()?.to_string()),
+        None => None
+    })
+}
+
+
+pub(crate) fn handle_from(author: Option<String>) -> Option<String> {
+    author.filter(|a| a != UNKNOWN_AUTHOR && ! a.trim().is_empty())
+        .map(|a| author_handle(&a))
+}
+
+
+pub(crate) fn read_zip_file<R: Read + Seek>(zip: &mut ZipArchive<R>, name: &str) -> Result<Option<String>> {
+    let mut file = match zip.by_name(name) {
+        Ok(file) => file,
+        Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+        Err(e) => return Err(e.into())
+    };
+    let mut text = String::new();
+    file.read_to_string(&mut text)?;
+    Ok(Some(text))
+}
@@ -10,7 +10,7 @@ fn suggs_output(args: &[&str]) -> String {
 }
 
 fn suggs_run(args: &[&str]) -> Output {
-    Command::new("target/debug/suggs")
+    Command::new(env!("CARGO_BIN_EXE_suggs"))
         .args(args)
         .output().unwrap()
 }
@@ -55,6 +55,22 @@ fn test_tex() {
 }
 
 
+#[test]
+fn test_html() {
+    assert_snapshot!(suggs_output(&["html", "resources/suggestions-simple.txt"]));
+    let document = suggs_output(&["html", "--standalone", "resources/suggestions-simple.txt"]);
+    assert!(document.starts_with("<!DOCTYPE html>"));
+    assert!(document.contains("<title>suggestions-simple.txt</title>"));
+    assert!(document.contains("<style>"));
+
+    let page = suggs_output(&["html", "--interactive", "resources/suggestions-nested.txt"]);
+    assert!(page.contains("<title>suggestions-nested.txt</title>"));
+    assert!(page.contains("<button id=\"download\">"));
+    assert!(page.contains("<span class=\"controls\"><button class=\"accept\""));
+    assert!(! page.contains("http"));
+}
+
+
 #[test]
 fn test_diff() {
     assert_snapshot!(suggs_output(&["diff", "resources/old.txt", "resources/new.txt"]));
@@ -74,3 +90,455 @@ fn test_wrong() {
         "resources/suggestions-bad-unmatched-opener.txt"], 
         "unmatched");
 }
+
+
+fn copy_to_tmp(resource: &str, name: &str) -> String {
+    let dir = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let dest = dir.join(std::path::Path::new(resource).file_name().unwrap());
+    std::fs::copy(resource, &dest).unwrap();
+    dest.to_string_lossy().to_string()
+}
+
+
+/// Make a git repository in `dir`, and return a function that runs git
+/// there as the user `who`, checks that it succeeded, and returns its
+/// output.
+fn git_repo(dir: &std::path::Path) -> impl Fn(&[&str], &str) -> Output + '_ {
+    let git = move |args: &[&str], who: &str| {
+        let output = Command::new("git")
+            .args(args)
+            .current_dir(dir)
+            .env("GIT_AUTHOR_NAME", who)
+            .env("GIT_AUTHOR_EMAIL", format!("{}@example.com", who))
+            .env("GIT_COMMITTER_NAME", who)
+            .env("GIT_COMMITTER_EMAIL", format!("{}@example.com", who))
+            .output().unwrap();
+        assert!(output.status.success(), "git {:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+        output
+    };
+    git(&["init", "-q", "-b", "main"], "alice");
+    git
+}
+
+
+/// Save `suggestions` in a temporary file, and return the output of
+/// `suggs old` and `suggs new` on it.
+fn old_and_new(suggestions: &str, name: &str) -> (String, String) {
+    let dir = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("suggestions.txt").to_string_lossy().to_string();
+    std::fs::write(&path, suggestions).unwrap();
+    (suggs_output(&["old", &path]), suggs_output(&["new", &path]))
+}
+
+
+#[test]
+fn test_accept_backup() {
+    let path = copy_to_tmp("resources/suggestions-simple.txt", "accept-backup");
+    let output = suggs_run(&["accept", "--backup=.orig", &path]);
+    assert!(output.status.success());
+
+    let accepted = std::fs::read_to_string(&path).unwrap();
+    let expected = suggs_output(&["new", "resources/suggestions-simple.txt"]);
+    assert_eq!(format!("{}\n", accepted), expected);
+    let backup = std::fs::read_to_string(format!("{}.orig", path)).unwrap();
+    assert_eq!(backup, std::fs::read_to_string("resources/suggestions-simple.txt").unwrap());
+}
+
+
+#[test]
+fn test_reject_dry_run() {
+    let path = copy_to_tmp("resources/suggestions-simple.txt", "reject-dry-run");
+    let output = suggs_run(&["reject", "--dry-run", &path]);
+    assert!(output.status.success());
+    let preview = String::from_utf8(output.stdout).unwrap();
+    assert!(preview.contains("Some text. --[++[An insertion.]++]-- More text."));
+    assert!(preview.contains("Some text. --[%%[A comment.]%%]-- More text."));
+    assert!(preview.contains("Some text. --[--[A deletion.]--]--++[A deletion.]++ More text."));
+    let preview = suggs_output(&["accept", "--dry-run", &path]);
+    assert_snapshot!("accept-dry-run", preview);
+
+    let after = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(after, std::fs::read_to_string("resources/suggestions-simple.txt").unwrap());
+    let dir = std::path::Path::new(&path).parent().unwrap();
+    assert_eq!(std::fs::read_dir(dir).unwrap().count(), 1);
+}
+
+
+#[test]
+fn test_accept_directory() {
+    let path = copy_to_tmp("resources/suggestions-simple.txt", "accept-directory");
+    let dir = std::path::Path::new(&path).parent().unwrap();
+    let nested = dir.join("nested");
+    std::fs::create_dir(&nested).unwrap();
+    std::fs::copy("resources/suggestions-nested.txt", nested.join("nested.txt")).unwrap();
+    std::fs::copy("resources/suggestions-bad-unmatched-closer.txt", nested.join("bad.txt")).unwrap();
+    std::fs::copy("resources/suggestions-tex.tex", dir.join("skipped.tex")).unwrap();
+
+    let output = suggs_run(&["accept", "--include", "*.txt", dir.to_str().unwrap()]);
+    assert!(! output.status.success());
+    let report = String::from_utf8_lossy(&output.stderr);
+    assert!(report.contains("failed:"));
+    assert!(report.contains("bad.txt"));
+    assert!(report.contains("1 of 3 files"));
+    assert!(! report.contains("skipped.tex"));
+
+    let accepted = std::fs::read_to_string(nested.join("nested.txt")).unwrap();
+    let expected = suggs_output(&["new", "resources/suggestions-nested.txt"]);
+    assert_eq!(format!("{}\n", accepted), expected);
+    let skipped = std::fs::read_to_string(dir.join("skipped.tex")).unwrap();
+    assert_eq!(skipped, std::fs::read_to_string("resources/suggestions-tex.tex").unwrap());
+
+    suggs_test_error(&["accept", "--exclude", "*", dir.to_str().unwrap()], "no files matched");
+}
+
+
+#[test]
+fn test_reject_include_exclude() {
+    let dir = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("reject-include-exclude");
+    let _ = std::fs::remove_dir_all(&dir);
+    let book = dir.join("book");
+    std::fs::create_dir_all(book.join("drafts")).unwrap();
+    let original = "Some ++[new ]++text.";
+    for file in ["chapter1.md", "drafts/d.md", "notes.txt"] {
+        std::fs::write(book.join(file), original).unwrap();
+    }
+
+    let output = Command::new(env!("CARGO_BIN_EXE_suggs"))
+        .args(["reject", "--include", "*.md", "--exclude", "drafts/*", "book/"])
+        .current_dir(&dir)
+        .output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(std::fs::read_to_string(book.join("chapter1.md")).unwrap(), "Some text.");
+    assert_eq!(std::fs::read_to_string(book.join("drafts/d.md")).unwrap(), original);
+    assert_eq!(std::fs::read_to_string(book.join("notes.txt")).unwrap(), original);
+}
+
+
+#[test]
+fn test_diff_granularity() {
+    for granularity in ["chars", "words", "sentences", "lines", "paragraphs"] {
+        assert_snapshot!(
+            format!("diff-{}", granularity),
+            suggs_output(&["diff", "--granularity", granularity, 
+                "resources/old-prose.txt", "resources/new-prose.txt"])
+        );
+    }
+}
+
+
+#[test]
+fn test_diff_base_suggestions() {
+    assert_snapshot!(suggs_output(&["diff", "--base-suggestions", "--author", "mark",
+        "resources/suggestions-base.txt", "resources/new-after-base.txt"]));
+}
+
+
+#[cfg(unix)]
+#[test]
+fn test_suggest() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let path = copy_to_tmp("resources/suggestions-base.txt", "suggest");
+    let dir = std::path::Path::new(&path).parent().unwrap();
+    let editor = dir.join("fake-editor.sh");
+    std::fs::write(&editor, "#!/bin/sh\nsed -i -e 's/resort/town/' -e 's/dreamt/dreamed/' \"$1\"\n").unwrap();
+    std::fs::set_permissions(&editor, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_suggs"))
+        .args(["suggest", "--author", "mark", &path])
+        .env_remove("VISUAL")
+        .env("EDITOR", &editor)
+        .output().unwrap();
+    assert!(output.status.success());
+    assert_snapshot!(std::fs::read_to_string(&path).unwrap());
+}
+
+
+#[test]
+fn test_diff_revisions() {
+    let output = suggs_output(&["diff", "--author", "@bob", 
+        "resources/draft-v1.txt", "resources/draft-v2.txt", 
+        "--author", "@carol", "resources/draft-v3.txt"]);
+    assert_snapshot!(output);
+    let from_manifest = suggs_output(&["diff", "--manifest", "resources/drafts-manifest.txt"]);
+    assert_eq!(output, from_manifest);
+
+    let (old, new) = old_and_new(&output, "diff-revisions");
+    assert_eq!(old.trim_end(), std::fs::read_to_string("resources/draft-v1.txt").unwrap().trim_end());
+    assert_eq!(new.trim_end(), std::fs::read_to_string("resources/draft-v3.txt").unwrap().trim_end());
+
+    let output = Command::new(env!("CARGO_BIN_EXE_suggs"))
+        .args(["diff", "resources/draft-v1.txt", "resources/draft-v2.txt"])
+        .env("SUGGS_AUTHOR", "Jane Doe")
+        .output().unwrap();
+    assert!(! output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("contains spaces"));
+}
+
+
+#[test]
+fn test_merge() {
+    let args = ["merge", "resources/merge-original.txt", 
+        "resources/merge-alice.txt", "resources/merge-bob.txt"];
+    assert_snapshot!(suggs_output(&args));
+    let output = suggs_run(&args);
+    let report = String::from_utf8_lossy(&output.stderr);
+    assert!(report.contains("line 2"));
+    assert!(report.contains("@alice, @bob"));
+
+    suggs_test_error(&["merge", "resources/merge-original.txt",
+        "resources/merge-alice.txt", "resources/suggestions-simple.txt"], 
+        "not made from the original");
+}
+
+
+#[test]
+fn test_merge_unsigned_insertions() {
+    let dir = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("merge-unsigned");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = |name: &str| dir.join(name).to_string_lossy().to_string();
+    std::fs::write(path("original.txt"), "The cat sat.").unwrap();
+    std::fs::write(path("ours.txt"), "The ++[black ]++cat sat.").unwrap();
+    std::fs::write(path("theirs.txt"), "The ++[fat ]++cat sat.").unwrap();
+
+    let output = suggs_run(&["merge", &path("original.txt"), &path("ours.txt"), &path("theirs.txt")]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Conflicting changes at line 1"));
+
+    // the merge driver fails, so git marks the file as conflicted
+    let output = suggs_run(&["merge-driver", &path("original.txt"), &path("ours.txt"), &path("theirs.txt")]);
+    assert!(! output.status.success());
+    assert_eq!(std::fs::read_to_string(path("ours.txt")).unwrap().trim_end(), 
+        "The ++[black ]++++[fat ]++cat sat.");
+}
+
+
+#[test]
+fn test_rebase() {
+    let args = ["rebase", "resources/merge-alice.txt", "resources/rebase-new-base.txt"];
+    assert_snapshot!(suggs_output(&args));
+    let output = suggs_run(&args);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("\"quick\""));
+}
+
+
+#[test]
+fn test_conflicts() {
+    assert_snapshot!(suggs_output(&["conflicts", "resources/overlaps.txt"]));
+}
+
+
+#[test]
+fn test_from_patch() {
+    let output = suggs_output(&["from-patch", "resources/prose.patch"]);
+    assert_snapshot!(output);
+    let (old, new) = old_and_new(&output, "from-patch");
+    assert_eq!(old.trim_end(), std::fs::read_to_string("resources/old-prose.txt").unwrap().trim_end());
+    assert_eq!(new.trim_end(), "Mr. Darcy arrived at noon. He was, by his own account, exhausted.\n\
+        The party began at once.\n\nElizabeth danced twice.");
+    suggs_test_error(&["from-patch", "--source", "resources/merge-original.txt", "resources/prose.patch"], 
+        "does not apply");
+}
+
+
+#[test]
+fn test_to_patch() {
+    assert_snapshot!(suggs_output(&["to-patch", "--comments", "-U", "1", "resources/suggestions-multiline.txt"]));
+}
+
+
+#[test]
+fn test_diff_git() {
+    let path = copy_to_tmp("resources/draft-v1.txt", "diff-git");
+    let dir = std::path::Path::new(&path).parent().unwrap();
+    let git = git_repo(dir);
+    git(&["add", "draft-v1.txt"], "alice");
+    git(&["commit", "-q", "-m", "v1"], "alice");
+    for (version, who) in [("resources/draft-v2.txt", "bob"), ("resources/draft-v3.txt", "carol")] {
+        std::fs::copy(version, &path).unwrap();
+        git(&["commit", "-q", "-a", "-m", version], who);
+    }
+    std::fs::write(&path, "It was a dark and stormy night. The rain fell in torrents.\n").unwrap();
+
+    let range = suggs_output(&["diff", "--git-range", "HEAD~2..HEAD", &path]);
+    assert_snapshot!("diff-git-range", range);
+    let (old, new) = old_and_new(&range, "diff-git-range");
+    assert_eq!(old.trim_end(), std::fs::read_to_string("resources/draft-v1.txt").unwrap().trim_end());
+    assert_eq!(new.trim_end(), std::fs::read_to_string("resources/draft-v3.txt").unwrap().trim_end());
+
+    let output = Command::new(env!("CARGO_BIN_EXE_suggs"))
+        .args(["diff", "--git", "HEAD~1", &path])
+        .env("GIT_AUTHOR_NAME", "Dave")
+        .env("GIT_AUTHOR_EMAIL", "dave@example.com")
+        .output().unwrap();
+    assert_snapshot!("diff-git", String::from_utf8(output.stdout).unwrap());
+
+    let with_author = suggs_output(&["diff", "--author", "@me", "--git-range", "HEAD~2..HEAD", &path]);
+    assert!(with_author.contains("@me") && ! with_author.contains("@bob"));
+    suggs_test_error(&["diff", "--git", "no-such-revision", &path], "could not read");
+}
+
+
+#[test]
+fn test_git_diff_driver() {
+    let path = copy_to_tmp("resources/draft-v1.txt", "git-diff-driver");
+    let dir = std::path::Path::new(&path).parent().unwrap();
+    let git = git_repo(dir);
+    git(&["add", "draft-v1.txt"], "alice");
+    git(&["commit", "-q", "-m", "v1"], "alice");
+    std::fs::copy("resources/draft-v2.txt", &path).unwrap();
+
+    let driver = Command::new(env!("CARGO_BIN_EXE_suggs"))
+        .args(["git-diff-driver", "draft-v1.txt", "resources/draft-v1.txt", 
+            "0000000", "100644", "resources/draft-v2.txt", "0000000", "100644"])
+        .output().unwrap();
+    let driver = String::from_utf8(driver.stdout).unwrap();
+    assert_snapshot!(driver);
+
+    let install = Command::new(env!("CARGO_BIN_EXE_suggs"))
+        .args(["git-install-driver", "--pattern", "*.txt"])
+        .current_dir(dir)
+        .output().unwrap();
+    assert!(install.status.success());
+    // installing twice doesn't repeat the attribute
+    Command::new(env!("CARGO_BIN_EXE_suggs"))
+        .args(["git-install-driver", "--pattern", "*.txt"])
+        .current_dir(dir)
+        .output().unwrap();
+    let attributes = std::fs::read_to_string(dir.join(".gitattributes")).unwrap();
+    assert_eq!(attributes, "*.txt diff=suggs\n");
+
+    let git_diff = String::from_utf8(git(&["diff"], "alice").stdout).unwrap();
+    assert_eq!(git_diff, driver);
+
+    // git passes two more arguments for a renamed file
+    git(&["commit", "-q", "-a", "-m", "v2"], "alice");
+    git(&["mv", "draft-v1.txt", "renamed.txt"], "alice");
+    let text = std::fs::read_to_string("resources/draft-v2.txt").unwrap();
+    std::fs::write(dir.join("renamed.txt"), format!("{}The end.\n", text)).unwrap();
+    git(&["add", "renamed.txt"], "alice");
+    let renamed = git(&["diff", "-M", "--cached"], "alice");
+    let renamed = String::from_utf8(renamed.stdout).unwrap();
+    assert_eq!(renamed, "diff --suggs a/draft-v1.txt b/renamed.txt\n\
+        It was a dark and stormy night. The rain fell.\n++[The end.\n]++\n");
+}
+
+
+#[test]
+fn test_merge_driver() {
+    let path = copy_to_tmp("resources/merge-original.txt", "merge-driver");
+    let dir = std::path::Path::new(&path).parent().unwrap();
+    let git = git_repo(dir);
+    let driver = format!("\"{}\" merge-driver %O %A %B", env!("CARGO_BIN_EXE_suggs"));
+    git(&["config", "merge.suggs.driver", &driver], "alice");
+    std::fs::write(dir.join(".gitattributes"), "*.txt merge=suggs\n").unwrap();
+    git(&["add", "."], "alice");
+    git(&["commit", "-q", "-m", "original"], "alice");
+
+    git(&["checkout", "-q", "-b", "bob"], "bob");
+    std::fs::write(&path, "The quick brown fox jumps over the lazy dog.\n\
+        It was the best of times++[, it was the worst of times @bob]++.\n").unwrap();
+    git(&["commit", "-q", "-a", "-m", "bob"], "bob");
+    git(&["checkout", "-q", "main"], "alice");
+    std::fs::write(&path, "The ++[very @alice ]++quick brown fox jumps over the lazy dog.\n\
+        It was the best of times.\n").unwrap();
+    git(&["commit", "-q", "-a", "-m", "alice"], "alice");
+
+    // the driver combines both branches' suggestions without a conflict
+    git(&["merge", "-q", "--no-edit", "bob"], "alice");
+    assert_snapshot!(std::fs::read_to_string(&path).unwrap());
+
+    // conflicting changes leave a valid file and a failed merge
+    let ours = copy_to_tmp("resources/merge-alice.txt", "merge-driver-conflict");
+    let output = suggs_run(&["merge-driver", "resources/merge-original.txt", &ours, "resources/merge-bob.txt"]);
+    assert!(! output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("line 2"));
+    let merged = suggs_output(&["old", &ours]);
+    assert_eq!(merged.trim_end(), std::fs::read_to_string("resources/merge-original.txt").unwrap().trim_end());
+}
+
+
+#[test]
+fn test_blame() {
+    let path = copy_to_tmp("resources/draft-v1.txt", "blame");
+    let dir = std::path::Path::new(&path).parent().unwrap();
+    let git = git_repo(dir);
+    git(&["add", "draft-v1.txt"], "alice");
+    git(&["commit", "-q", "-m", "v1"], "alice");
+    for (version, who) in [("resources/draft-v2.txt", "bob"), ("resources/draft-v3.txt", "carol")] {
+        std::fs::copy(version, &path).unwrap();
+        git(&["commit", "-q", "-a", "-m", version], who);
+    }
+
+    let v1 = std::fs::read_to_string("resources/draft-v1.txt").unwrap();
+    let v3 = std::fs::read_to_string("resources/draft-v3.txt").unwrap();
+    let since = suggs_output(&["blame", "--since", "HEAD~2", 
+        "--authors", "resources/blame-authors.txt", &path]);
+    assert_snapshot!("blame-since", since);
+    let (old, new) = old_and_new(&since, "blame-since");
+    assert_eq!((old.trim_end(), new.trim_end()), (v1.trim_end(), v3.trim_end()));
+
+    let all = suggs_output(&["blame", &path]);
+    assert_snapshot!("blame-all", all);
+    let (old, new) = old_and_new(&all, "blame-all");
+    assert_eq!((old.trim_end(), new.trim_end()), ("", v3.trim_end()));
+}
+
+
+#[test]
+fn test_convert_criticmarkup() {
+    assert_snapshot!("from-criticmarkup", 
+        suggs_output(&["convert", "--from", "criticmarkup", "resources/criticmarkup.md"]));
+    assert_snapshot!("to-criticmarkup", 
+        suggs_output(&["convert", "--to", "criticmarkup", "resources/suggestions-simple.txt"]));
+    suggs_test_error(&["convert", "--to", "criticmarkup", "resources/suggestions-nested.txt"], "nested");
+}
+
+
+#[test]
+fn test_docx() {
+    use std::io::Read;
+
+    let dir = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("docx");
+    std::fs::create_dir_all(&dir).unwrap();
+    let output = dir.join("simple.docx").to_string_lossy().to_string();
+    suggs_run(&["docx", "resources/suggestions-simple.txt", "-o", &output]);
+
+    let mut zip = zip::ZipArchive::new(std::fs::File::open(&output).unwrap()).unwrap();
+    let mut document = String::new();
+    zip.by_name("word/document.xml").unwrap().read_to_string(&mut document).unwrap();
+    assert_eq!(document.matches("<w:p>").count(), 7);
+    assert!(document.contains("<w:ins w:id=\"1\" w:author=\"@author1\"><w:r><w:t xml:space=\"preserve\">A signed insertion.</w:t>"));
+    assert!(document.contains("<w:delText xml:space=\"preserve\">A deletion.</w:delText>"));
+    let mut comments = String::new();
+    zip.by_name("word/comments.xml").unwrap().read_to_string(&mut comments).unwrap();
+    assert_eq!(comments.matches("<w:comment ").count(), 2);
+
+    assert_snapshot!(suggs_output(&["from-docx", &output]));
+    suggs_test_error(&["from-docx", "resources/suggestions-simple.txt"], "not a word document");
+}
+
+
+#[test]
+fn test_odt() {
+    use std::io::Read;
+
+    let dir = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("odt");
+    std::fs::create_dir_all(&dir).unwrap();
+    let output = dir.join("simple.odt").to_string_lossy().to_string();
+    suggs_run(&["odt", "resources/suggestions-simple.txt", "-o", &output]);
+
+    let mut zip = zip::ZipArchive::new(std::fs::File::open(&output).unwrap()).unwrap();
+    let mut content = String::new();
+    zip.by_name("content.xml").unwrap().read_to_string(&mut content).unwrap();
+    assert_eq!(content.matches("<text:changed-region ").count(), 4);
+    assert!(content.contains("<text:insertion><office:change-info><dc:creator>@author1</dc:creator>"));
+    assert!(content.contains("<text:p>A deletion.</text:p></text:deletion>"));
+    assert_eq!(content.matches("<office:annotation>").count(), 2);
+
+    assert_snapshot!(suggs_output(&["from-odt", &output]));
+    suggs_test_error(&["from-odt", "resources/suggestions-simple.txt"], "not an opendocument file");
+}